@@ -0,0 +1,151 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant};
+
+/// 与 curl 相同, 上一个地址 200 毫秒内没有连上就开始尝试下一个
+pub const HAPPY_EYEBALLS_TIMEOUT: Duration = Duration::from_millis(200);
+
+type Attempt = (SocketAddr, io::Result<TcpStream>);
+
+/// Happy Eyeballs (RFC 8305): 按顺序 (已经按地址族交替排列) 发起连接,
+/// 每隔 stagger 或上一个失败时开始下一个, 返回最先成功的连接.
+/// 每个地址最多连接 attempt_timeout (--connect-timeout), 整个过程不超过 deadline (-m).
+/// 每个连接在单独的线程中进行, 落后的连接成功后直接丢弃
+pub fn race(
+    addrs: &[SocketAddr],
+    stagger: Duration,
+    attempt_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    mut log: impl FnMut(&str),
+) -> io::Result<(TcpStream, SocketAddr)> {
+    let (tx, rx) = mpsc::channel::<Attempt>();
+    let mut next = 0;
+    let mut pending = 0;
+    let mut next_start = Instant::now();
+    let mut last_err = None;
+    loop {
+        let now = Instant::now();
+        if next < addrs.len() && (pending == 0 || now >= next_start) {
+            let addr = addrs[next];
+            log(&format!("  Trying {}...", addr));
+            let timeout = attempt_limit(attempt_timeout, deadline, now);
+            let tx = tx.clone();
+            std::thread::spawn(move || {
+                let result = match timeout {
+                    Some(timeout) if timeout.is_zero() => Err(timed_out()),
+                    Some(timeout) => TcpStream::connect_timeout(&addr, timeout),
+                    None => TcpStream::connect(addr),
+                };
+                let _ = tx.send((addr, result));
+            });
+            next += 1;
+            pending += 1;
+            next_start = now + stagger;
+            continue;
+        }
+        if pending == 0 {
+            break;
+        }
+
+        // 等到下一个地址开始或者超时
+        let wake = match (next < addrs.len(), deadline) {
+            (true, Some(deadline)) => Some(next_start.min(deadline)),
+            (true, None) => Some(next_start),
+            (false, deadline) => deadline,
+        };
+        let received = match wake {
+            Some(wake) => rx.recv_timeout(wake.saturating_duration_since(now)),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match received {
+            Ok((addr, Ok(stream))) => return Ok((stream, addr)),
+            Ok((addr, Err(e))) => {
+                let e = if is_timeout(&e) { timed_out() } else { e };
+                log(&format!("connect to {} failed: {}", addr, e));
+                last_err = Some(e);
+                pending -= 1;
+                // 失败后立即尝试下一个地址
+                next_start = Instant::now();
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    return Err(timed_out());
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+    Err(last_err
+        .unwrap_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect")))
+}
+
+/// 一个地址的连接时间: --connect-timeout 和 -m 剩余时间中较小的一个
+fn attempt_limit(
+    attempt_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    now: Instant,
+) -> Option<Duration> {
+    let remaining = deadline.map(|d| d.saturating_duration_since(now));
+    match (attempt_timeout, remaining) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+    )
+}
+
+fn timed_out() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "connection timed out")
+}
+
+#[test]
+fn test_race() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let good = listener.local_addr().unwrap();
+    // 端口未监听, 连接立即被拒绝
+    let refused = {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        closed.local_addr().unwrap()
+    };
+
+    // 第一个地址失败后不等 stagger, 立即尝试下一个
+    let start = Instant::now();
+    let mut lines = Vec::new();
+    let (_, addr) = race(&[refused, good], Duration::from_secs(5), None, None, |l| {
+        lines.push(l.to_string())
+    })
+    .unwrap();
+    assert_eq!(addr, good);
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert_eq!(lines.len(), 3);
+    assert!(lines[1].starts_with(&format!("connect to {} failed", refused)));
+
+    let err = race(&[refused], Duration::from_secs(5), None, None, |_| {}).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+    let past = Instant::now();
+    let err = race(&[good], Duration::ZERO, None, Some(past), |_| {}).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    let err = race(&[good], Duration::ZERO, Some(Duration::ZERO), None, |_| {}).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+    // --connect-timeout 对每个地址单独计算, 只受 -m 剩余时间限制
+    let s = Duration::from_secs;
+    let now = Instant::now();
+    assert_eq!(attempt_limit(Some(s(3)), None, now + s(10)), Some(s(3)));
+    assert_eq!(attempt_limit(Some(s(3)), Some(now + s(2)), now), Some(s(2)));
+    assert_eq!(
+        attempt_limit(Some(s(3)), Some(now + s(10)), now + s(9)),
+        Some(s(1))
+    );
+    assert_eq!(attempt_limit(None, Some(now + s(2)), now), Some(s(2)));
+    assert_eq!(attempt_limit(None, None, now), None);
+}
@@ -0,0 +1,248 @@
+use std::io::{Read, Write};
+use std::net::IpAddr;
+use std::time::Duration;
+
+use rustls_pki_types::ServerName;
+use trust_dns_resolver::proto::op::{Message, MessageType, OpCode, Query, ResponseCode};
+use trust_dns_resolver::proto::rr::{Name, RData, RecordType};
+use url::Url;
+
+use crate::connect;
+use crate::parser::{self, IpFamily, ResolveOptions};
+use crate::requester;
+use crate::response::{self, BodyReader, Response};
+
+// DoH 服务器没有响应时不一直等待
+const DOH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 通过 DNS-over-HTTPS (RFC 8484) 查询 domain 的地址, A 和 AAAA 分别发送一个 POST 请求.
+/// DoH 服务器自身的 host 按 opts 中的其他配置解析. 返回地址和最小的 TTL
+pub fn lookup(
+    doh_url: &Url,
+    domain: &str,
+    opts: &ResolveOptions,
+) -> Result<(Vec<IpAddr>, Option<Duration>), Box<dyn std::error::Error>> {
+    let types: &[RecordType] = match opts.family {
+        IpFamily::Any => &[RecordType::AAAA, RecordType::A],
+        IpFamily::V4 => &[RecordType::A],
+        IpFamily::V6 => &[RecordType::AAAA],
+    };
+    let mut name = Name::from_ascii(domain)?;
+    name.set_fqdn(true);
+
+    let mut ips = Vec::new();
+    let mut ttl = None;
+    for &record_type in types {
+        let query = query_message(&name, record_type).to_vec()?;
+        let reply = Message::from_vec(&post(doh_url, &query, opts)?)?;
+        let answers = answer_ips(&reply, &name, record_type)?;
+        ttl = answers.iter().map(|&(_, t)| t).chain(ttl).min();
+        ips.extend(answers.into_iter().map(|(ip, _)| ip));
+    }
+    if ips.is_empty() {
+        return Err(format!("no address for {} from DoH server", domain).into());
+    }
+    Ok((ips, ttl.map(|t| Duration::from_secs(t.into()))))
+}
+
+fn query_message(name: &Name, record_type: RecordType) -> Message {
+    let mut msg = Message::new();
+    // RFC 8484 建议 id 为 0, 便于缓存
+    msg.set_id(0)
+        .set_message_type(MessageType::Query)
+        .set_op_code(OpCode::Query)
+        .set_recursion_desired(true)
+        .add_query(Query::query(name.clone(), record_type));
+    msg
+}
+
+/// 检查应答对应发出的查询, 返回其中 record_type 类型的地址和各自的 TTL.
+/// CNAME 等其他记录不计入 TTL
+fn answer_ips(
+    reply: &Message,
+    name: &Name,
+    record_type: RecordType,
+) -> Result<Vec<(IpAddr, u32)>, Box<dyn std::error::Error>> {
+    if reply.message_type() != MessageType::Response
+        || reply.queries() != [Query::query(name.clone(), record_type)]
+    {
+        return Err("DoH server answered a different query".into());
+    }
+    if reply.response_code() != ResponseCode::NoError {
+        return Err(format!("DoH server answered {}", reply.response_code()).into());
+    }
+    Ok(reply
+        .answers()
+        .iter()
+        .filter_map(|record| match (record.rdata(), record_type) {
+            (RData::A(ip), RecordType::A) => Some((IpAddr::V4(*ip), record.ttl())),
+            (RData::AAAA(ip), RecordType::AAAA) => Some((IpAddr::V6(*ip), record.ttl())),
+            _ => None,
+        })
+        .collect())
+}
+
+/// 发送 application/dns-message, 返回响应体. https 使用与普通请求相同的 rustls 配置
+fn post(
+    doh_url: &Url,
+    query: &[u8],
+    opts: &ResolveOptions,
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let host = doh_url.host().ok_or("DoH URL must have a host")?.to_owned();
+    let port = doh_url
+        .port_or_known_default()
+        .ok_or("unsupported DoH URL scheme")?;
+    let opts = ResolveOptions {
+        doh_url: None,
+        ..opts.clone()
+    };
+    let addrs =
+        parser::to_adders(&host, port, &opts).map_err(|e| e as Box<dyn std::error::Error>)?;
+    // 与普通请求一样竞速连接, 每个地址最多等待 DOH_TIMEOUT
+    let (stream, _) = connect::race(
+        &addrs,
+        connect::HAPPY_EYEBALLS_TIMEOUT,
+        Some(DOH_TIMEOUT),
+        None,
+        |_| {},
+    )?;
+    stream.set_read_timeout(Some(DOH_TIMEOUT))?;
+    stream.set_write_timeout(Some(DOH_TIMEOUT))?;
+
+    match doh_url.scheme() {
+        "https" => {
+            let server_name =
+                ServerName::try_from(parser::bare_host(doh_url).unwrap_or(""))?.to_owned();
+            let conn = rustls::ClientConnection::new(requester::tls_config()?, server_name)?;
+            exchange(&mut rustls::StreamOwned::new(conn, stream), doh_url, query)
+        }
+        "http" => exchange(&mut &stream, doh_url, query),
+        scheme => Err(format!("unsupported DoH URL scheme: {}", scheme).into()),
+    }
+}
+
+fn exchange<S: Read + Write>(
+    stream: &mut S,
+    doh_url: &Url,
+    query: &[u8],
+) -> Result<Vec<u8>, Box<dyn std::error::Error>> {
+    let mut host = doh_url.host_str().unwrap_or_default().to_string();
+    if let Some(port) = doh_url.port() {
+        host = format!("{}:{}", host, port);
+    }
+    let head = format!(
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/dns-message\r\nAccept: application/dns-message\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        &doh_url[url::Position::BeforePath..],
+        host,
+        query.len()
+    );
+    stream.write_all(head.as_bytes())?;
+    stream.write_all(query)?;
+    stream.flush()?;
+
+    let (resp, pending) = Response::read_head(stream)?;
+    if resp.status != 200 {
+        return Err(format!("DoH server returned HTTP {}", resp.status).into());
+    }
+    if response::get_header(&resp.headers, "Content-Type")
+        .is_some_and(|ct| !ct.starts_with("application/dns-message"))
+    {
+        return Err("DoH server did not return application/dns-message".into());
+    }
+    let mut body = BodyReader::new(stream, &resp, pending, true)?;
+    let mut reply = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match body.read(&mut buf)? {
+            0 => return Ok(reply),
+            n => reply.extend_from_slice(&buf[..n]),
+        }
+    }
+}
+
+/// 测试用的 DNS 应答: 对每个查询返回 A 或 AAAA 记录
+#[cfg(test)]
+pub fn stub_reply(query: &[u8], ips: &[IpAddr]) -> Vec<u8> {
+    use trust_dns_resolver::proto::rr::Record;
+
+    let mut msg = Message::from_vec(query).unwrap();
+    msg.set_message_type(MessageType::Response);
+    let q = msg.queries()[0].clone();
+    for ip in ips {
+        let rdata = match (ip, q.query_type()) {
+            (IpAddr::V4(ip), RecordType::A) => RData::A(*ip),
+            (IpAddr::V6(ip), RecordType::AAAA) => RData::AAAA(*ip),
+            _ => continue,
+        };
+        msg.add_answer(Record::from_rdata(q.name().clone(), 60, rdata));
+    }
+    msg.to_vec().unwrap()
+}
+
+#[test]
+fn test_doh_lookup() {
+    use std::net::TcpListener;
+
+    // 本地的 DoH 服务器, 每个连接应答一个查询
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let doh_url = Url::parse(&format!(
+        "http://{}/dns-query",
+        listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    let server = std::thread::spawn(move || {
+        let ips: [IpAddr; 2] = ["10.1.2.3".parse().unwrap(), "fd00::3".parse().unwrap()];
+        let mut requests = Vec::new();
+        for stream in listener.incoming().take(2) {
+            let mut stream = stream.unwrap();
+            let (head, body) = requester::read_request(&mut stream);
+            let reply = stub_reply(&body, &ips);
+            let head_line = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/dns-message\r\nContent-Length: {}\r\n\r\n",
+                reply.len()
+            );
+            stream.write_all(head_line.as_bytes()).unwrap();
+            stream.write_all(&reply).unwrap();
+            requests.push(head);
+        }
+        requests
+    });
+
+    let (ips, ttl) = lookup(&doh_url, "example.com", &ResolveOptions::default()).unwrap();
+    assert_eq!(ttl, Some(Duration::from_secs(60)));
+    assert_eq!(
+        ips,
+        [
+            "fd00::3".parse::<IpAddr>().unwrap(),
+            "10.1.2.3".parse().unwrap()
+        ]
+    );
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("POST /dns-query HTTP/1.1\r\n"));
+    assert!(requests[0].contains("Content-Type: application/dns-message\r\n"));
+}
+
+#[test]
+fn test_answer_ips() {
+    use trust_dns_resolver::proto::rr::Record;
+
+    let name = Name::from_ascii("example.com.").unwrap();
+    let query = query_message(&name, RecordType::A).to_vec().unwrap();
+    let ip: IpAddr = "10.1.2.3".parse().unwrap();
+
+    // CNAME 的 TTL 不计入
+    let mut reply = Message::from_vec(&stub_reply(&query, &[ip])).unwrap();
+    let alias = Name::from_ascii("alias.example.com.").unwrap();
+    reply.add_answer(Record::from_rdata(name.clone(), 5, RData::CNAME(alias)));
+    assert_eq!(
+        answer_ips(&reply, &name, RecordType::A).unwrap(),
+        [(ip, 60)]
+    );
+
+    // 查询不一致或者不是应答
+    assert!(answer_ips(&reply, &name, RecordType::AAAA).is_err());
+    let other = Name::from_ascii("other.example.").unwrap();
+    assert!(answer_ips(&reply, &other, RecordType::A).is_err());
+    reply.set_message_type(MessageType::Query);
+    assert!(answer_ips(&reply, &name, RecordType::A).is_err());
+}
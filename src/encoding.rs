@@ -0,0 +1,140 @@
+use std::io::{self, BufRead, BufReader, Read};
+
+/// 请求 --compressed 时发送的 Accept-Encoding
+pub const ACCEPT_ENCODING: &str = "gzip, deflate, br, zstd";
+
+/// Content-Encoding 支持的编码
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+    Zstd,
+}
+
+impl ContentEncoding {
+    pub fn from(name: &str) -> io::Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "" | "identity" => Ok(Self::Identity),
+            "gzip" | "x-gzip" => Ok(Self::Gzip),
+            "deflate" => Ok(Self::Deflate),
+            "br" => Ok(Self::Brotli),
+            "zstd" => Ok(Self::Zstd),
+            other => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported Content-Encoding: {}", other),
+            )),
+        }
+    }
+}
+
+/// 解析 Content-Encoding 头, 按应用顺序返回编码列表
+pub fn parse_encodings(header: &str) -> io::Result<Vec<ContentEncoding>> {
+    header.split(',').map(ContentEncoding::from).collect()
+}
+
+/// 包装 reader, 按相反的顺序逐层解码多重编码
+pub fn decoder<'a>(
+    encodings: &[ContentEncoding],
+    mut reader: Box<dyn Read + 'a>,
+) -> io::Result<Box<dyn Read + 'a>> {
+    for encoding in encodings.iter().rev() {
+        reader = match encoding {
+            ContentEncoding::Identity => reader,
+            ContentEncoding::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            ContentEncoding::Deflate => deflate_decoder(reader)?,
+            ContentEncoding::Brotli => {
+                Box::new(brotli_decompressor::Decompressor::new(reader, 4096))
+            }
+            ContentEncoding::Zstd => Box::new(
+                ruzstd::StreamingDecoder::new(reader)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?,
+            ),
+        };
+    }
+    Ok(reader)
+}
+
+/// HTTP 的 deflate 应为 zlib 格式, 但也有服务器直接发送原始 deflate 数据
+fn deflate_decoder<'a>(reader: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+    let mut reader = BufReader::new(reader);
+    let head = reader.fill_buf()?;
+    let is_zlib = head.len() >= 2
+        && head[0] & 0x0f == 8
+        && (u16::from(head[0]) << 8 | u16::from(head[1])) % 31 == 0;
+    if is_zlib {
+        Ok(Box::new(flate2::read::ZlibDecoder::new(reader)))
+    } else {
+        Ok(Box::new(flate2::read::DeflateDecoder::new(reader)))
+    }
+}
+
+#[cfg(test)]
+fn decode_all(encodings: &[ContentEncoding], body: &[u8]) -> io::Result<Vec<u8>> {
+    let mut reader = decoder(encodings, Box::new(body))?;
+    let mut buffer = [0u8; 4096];
+    let mut result = Vec::new();
+    loop {
+        let n = reader.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        result.extend_from_slice(&buffer[..n]);
+    }
+    Ok(result)
+}
+
+#[test]
+fn test_decode_stacked() {
+    use flate2::write::{DeflateEncoder, GzEncoder, ZlibEncoder};
+    use flate2::Compression;
+    use std::io::Write;
+
+    let text = b"hello hello hello compressed world";
+
+    // Content-Encoding: deflate, gzip -> 先 deflate 再 gzip
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(text).unwrap();
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(&zlib.finish().unwrap()).unwrap();
+    let body = gzip.finish().unwrap();
+
+    let encodings = parse_encodings("deflate, gzip").unwrap();
+    assert_eq!(
+        encodings,
+        vec![ContentEncoding::Deflate, ContentEncoding::Gzip]
+    );
+    assert_eq!(decode_all(&encodings, &body).unwrap(), text);
+
+    // 原始 deflate
+    let mut raw = DeflateEncoder::new(Vec::new(), Compression::default());
+    raw.write_all(text).unwrap();
+    let body = raw.finish().unwrap();
+    assert_eq!(
+        decode_all(&[ContentEncoding::Deflate], &body).unwrap(),
+        text
+    );
+
+    assert!(parse_encodings("compress").is_err());
+}
+
+#[test]
+fn test_decode_brotli_zstd() {
+    // 未压缩的 brotli meta-block: "hello"
+    let br = [0x40, 0x00, 0x10, b'h', b'e', b'l', b'l', b'o', 0x03];
+    assert_eq!(
+        decode_all(&[ContentEncoding::Brotli], &br).unwrap(),
+        b"hello"
+    );
+
+    // printf 'hello zstd' | zstd -c
+    let zst = [
+        0x28, 0xb5, 0x2f, 0xfd, 0x04, 0x58, 0x51, 0x00, 0x00, 0x68, 0x65, 0x6c, 0x6c, 0x6f, 0x20,
+        0x7a, 0x73, 0x74, 0x64, 0xcf, 0xdb, 0x60, 0x9c,
+    ];
+    assert_eq!(
+        decode_all(&[ContentEncoding::Zstd], &zst).unwrap(),
+        b"hello zstd"
+    );
+}
@@ -1,22 +1,28 @@
 use std::fs::{File, OpenOptions};
-use std::io::{self, Read, Write};
+use std::io::{self, Read};
 
 /// 字符串覆盖写入
+#[cfg(test)]
 pub fn write_string_to_file(path: &str, contents: &str) -> io::Result<()> {
-    let mut file = File::create(path)?;
-    file.write_all(contents.as_bytes())
+    std::fs::write(path, contents)
 }
 
-/// 字符串追加写入
-pub fn append_string_to_file(path: &str, contents: &str) -> io::Result<()> {
-    let mut file = OpenOptions::new().append(true).create(true).open(path)?;
-    file.write_all(contents.as_bytes())
+/// 打开输出文件, append 为 true 时追加写入, 否则覆盖
+pub fn open_output_file(path: &str, append: bool) -> io::Result<File> {
+    if append {
+        OpenOptions::new().append(true).create(true).open(path)
+    } else {
+        File::create(path)
+    }
 }
 
-/// 二进制覆盖写入
-pub fn write_bytes_to_file(path: &str, data: &[u8]) -> io::Result<()> {
-    let mut file = File::create(path)?;
-    file.write_all(data)
+/// 文件大小, 文件不存在时返回 0
+pub fn file_size(path: &str) -> io::Result<u64> {
+    match std::fs::metadata(path) {
+        Ok(meta) => Ok(meta.len()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
 }
 
 /// 二进制读取
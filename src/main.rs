@@ -1,11 +1,23 @@
-use clap::Parser;
-use url::Url;
+use clap::{CommandFactory, Parser};
+use std::io::IsTerminal;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+mod connect;
+mod doh;
+mod encoding;
 mod file_io;
+mod multipart;
 mod parser;
+mod pool;
+mod progress;
+mod proxy;
 mod requester;
 mod response;
 mod structs;
+mod timing;
+mod trace;
+mod writeout;
 
 #[derive(Parser)]
 #[command(name = "mini-curl", version = "1.0", about = "A curl-like tool")]
@@ -43,9 +55,9 @@ struct Cli {
         long = "output",
         value_name("STRING"),
         num_args = 1,
-        help = "Set output file for response"
+        help = "Set output file for response, one per URL in order"
     )]
-    output: Option<String>,
+    output: Vec<String>,
 
     #[arg(
         short = 'F',
@@ -56,53 +68,501 @@ struct Cli {
     )]
     formdata: Vec<String>,
 
+    #[arg(
+        short = 'C',
+        long = "continue-at",
+        value_name("OFFSET"),
+        allow_hyphen_values = true,
+        help = "Resume transfer at OFFSET, '-' to use the size of the output file"
+    )]
+    continue_at: Option<String>,
+
+    #[arg(
+        short = 'r',
+        long = "range",
+        value_name("RANGE"),
+        conflicts_with = "continue_at",
+        help = "Retrieve only the bytes within RANGE, e.g. 0-499,1000-1499"
+    )]
+    range: Option<String>,
+
+    #[arg(
+        long = "compressed",
+        help = "Request a compressed response and decode it"
+    )]
+    compressed: bool,
+
+    #[arg(
+        short = 'i',
+        long = "include",
+        help = "Include response headers in the output"
+    )]
+    include: bool,
+
+    #[arg(
+        short = 'I',
+        long = "head",
+        conflicts_with_all = ["data", "formdata"],
+        help = "Fetch the headers only, using a HEAD request"
+    )]
+    head: bool,
+
+    #[arg(
+        short = 'v',
+        long = "verbose",
+        help = "Show connection details and request/response headers"
+    )]
+    verbose: bool,
+
+    #[arg(
+        long = "trace",
+        value_name("FILE"),
+        conflicts_with = "trace_ascii",
+        help = "Write a hex and ASCII dump of all incoming and outgoing data to FILE, '-' for stdout"
+    )]
+    trace: Option<String>,
+
+    #[arg(
+        long = "trace-ascii",
+        value_name("FILE"),
+        help = "Like --trace, but without the hex output"
+    )]
+    trace_ascii: Option<String>,
+
+    #[arg(
+        long = "trace-time",
+        help = "Add time stamps to trace and verbose output"
+    )]
+    trace_time: bool,
+
+    #[arg(
+        short = 'w',
+        long = "write-out",
+        value_name("FORMAT"),
+        help = "Output FORMAT after each transfer, e.g. '%{http_code}\\n', '@file' reads it from a file"
+    )]
+    write_out: Option<String>,
+
+    #[arg(
+        short = '#',
+        long = "progress-bar",
+        help = "Display transfer progress as a bar"
+    )]
+    progress_bar: bool,
+
+    #[arg(short = 's', long = "silent", help = "Do not show the progress meter")]
+    silent: bool,
+
+    #[arg(short = 'L', long = "location", help = "Follow redirects")]
+    location: bool,
+
+    #[arg(
+        long = "max-redirs",
+        value_name("NUM"),
+        default_value_t = 50,
+        allow_negative_numbers = true,
+        help = "Maximum number of redirects allowed, -1 for unlimited"
+    )]
+    max_redirs: i64,
+
+    #[arg(
+        long = "connect-timeout",
+        value_name("SECONDS"),
+        value_parser = parse_seconds,
+        help = "Maximum time allowed for connecting to each address"
+    )]
+    connect_timeout: Option<Duration>,
+
+    #[arg(
+        short = 'm',
+        long = "max-time",
+        value_name("SECONDS"),
+        value_parser = parse_seconds,
+        help = "Maximum time allowed for the whole transfer"
+    )]
+    max_time: Option<Duration>,
+
+    #[arg(
+        long = "retry",
+        value_name("NUM"),
+        default_value_t = 0,
+        help = "Retry request if transient problems occur"
+    )]
+    retry: u32,
+
+    #[arg(
+        long = "retry-delay",
+        value_name("SECONDS"),
+        value_parser = parse_seconds,
+        help = "Wait time between retries instead of exponential backoff"
+    )]
+    retry_delay: Option<Duration>,
+
+    #[arg(
+        long = "retry-max-time",
+        value_name("SECONDS"),
+        value_parser = parse_seconds,
+        help = "Stop retrying after this many seconds"
+    )]
+    retry_max_time: Option<Duration>,
+
+    #[arg(
+        long = "retry-all-errors",
+        help = "Retry on all errors (use with --retry)"
+    )]
+    retry_all_errors: bool,
+
+    #[arg(
+        short = '4',
+        long = "ipv4",
+        help = "Resolve names to IPv4 addresses only"
+    )]
+    ipv4: bool,
+
+    #[arg(
+        short = '6',
+        long = "ipv6",
+        conflicts_with = "ipv4",
+        help = "Resolve names to IPv6 addresses only"
+    )]
+    ipv6: bool,
+
+    #[arg(
+        long = "resolver",
+        value_name("system|builtin"),
+        default_value = "builtin",
+        help = "Resolve names with the builtin DNS client or the system resolver"
+    )]
+    resolver: parser::ResolverKind,
+
+    #[arg(
+        long = "dns-servers",
+        value_name("ADDR[,ADDR]..."),
+        value_delimiter = ',',
+        value_parser = parser::parse_dns_server,
+        help = "DNS servers to use instead of the system nameservers (builtin resolver only)"
+    )]
+    dns_servers: Vec<std::net::SocketAddr>,
+
+    #[arg(
+        long = "doh-url",
+        value_name("URL"),
+        help = "Resolve host names over DNS-over-HTTPS"
+    )]
+    doh_url: Option<url::Url>,
+
+    #[arg(
+        long = "dns-cache-timeout",
+        value_name("SECONDS"),
+        default_value_t = 60,
+        allow_negative_numbers = true,
+        help = "Keep resolved addresses at most this long, 0 disables the cache, -1 keeps them until their TTL"
+    )]
+    dns_cache_timeout: i64,
+
+    #[arg(
+        long = "resolve",
+        value_name("HOST:PORT:ADDR[,ADDR]..."),
+        help = "Resolve HOST:PORT to the given addresses"
+    )]
+    resolve: Vec<parser::ResolveOverride>,
+
+    #[arg(
+        long = "connect-to",
+        value_name("HOST:PORT:CONNECT-TO-HOST:CONNECT-TO-PORT"),
+        help = "Connect to another host and port instead, keeping Host and SNI"
+    )]
+    connect_to: Vec<parser::ConnectTo>,
+
+    #[arg(
+        long = "happy-eyeballs-timeout-ms",
+        value_name("MILLISECONDS"),
+        default_value_t = 200,
+        help = "Start the next connection attempt after this many milliseconds"
+    )]
+    happy_eyeballs_timeout_ms: u64,
+
+    #[arg(
+        short = 'x',
+        long = "proxy",
+        value_name("[PROTOCOL://]HOST[:PORT]"),
+        help = "Use this proxy (http, socks4, socks4a, socks5, socks5h), https over http goes through a CONNECT tunnel"
+    )]
+    proxy: Option<proxy::Proxy>,
+
+    #[arg(
+        long = "socks4a",
+        value_name("HOST[:PORT]"),
+        value_parser = proxy::parse_socks4a,
+        help = "Use this SOCKS4a proxy, the proxy resolves host names"
+    )]
+    socks4a: Option<proxy::Proxy>,
+
+    #[arg(
+        long = "socks5",
+        value_name("HOST[:PORT]"),
+        value_parser = proxy::parse_socks5,
+        help = "Use this SOCKS5 proxy, host names are resolved locally"
+    )]
+    socks5: Option<proxy::Proxy>,
+
+    #[arg(
+        long = "socks5-hostname",
+        value_name("HOST[:PORT]"),
+        value_parser = proxy::parse_socks5_hostname,
+        help = "Use this SOCKS5 proxy, the proxy resolves host names"
+    )]
+    socks5_hostname: Option<proxy::Proxy>,
+
+    #[arg(
+        long = "proxy-user",
+        value_name("USER:PASSWORD"),
+        help = "User name and password for the proxy"
+    )]
+    proxy_user: Option<String>,
+
+    #[arg(
+        long = "proxytunnel",
+        help = "Tunnel plain http requests through the proxy with CONNECT too"
+    )]
+    proxytunnel: bool,
+
+    #[arg(
+        long = "pool-idle-timeout",
+        value_name("SECONDS"),
+        default_value_t = 60,
+        help = "Close pooled keep-alive connections idle for longer than this"
+    )]
+    pool_idle_timeout: u64,
+
+    #[arg(
+        long = "pool-max-per-host",
+        value_name("NUM"),
+        default_value_t = 4,
+        help = "Maximum idle connections kept per host, 0 disables reuse"
+    )]
+    pool_max_per_host: usize,
+
     #[arg(required = true)]
-    url: String,
+    url: Vec<String>,
 }
 
 fn main() {
     // 定义命令行界面
     let args = Cli::parse();
-    let url_str = args.url;
-    let url = Url::parse(&url_str).unwrap();
-    let mut request = requester::Request::new(&url);
-
-    
-    if let Some(data) = args.data {
-        request.set_data(&data);
+    // 系统解析使用 getaddrinfo, 无法指定 DNS 服务器
+    if args.resolver == parser::ResolverKind::System && !args.dns_servers.is_empty() {
+        Cli::command()
+            .error(
+                clap::error::ErrorKind::ArgumentConflict,
+                "--dns-servers cannot be used with --resolver system",
+            )
+            .exit();
     }
-    if !args.formdata.is_empty() {
-        let (boundary, body) = requester::build_formdata(&args.formdata).unwrap();
-        request.set_formdata(&body, boundary);
+
+    // 同一进程内的多个 URL 共享连接池
+    let pool = Arc::new(Mutex::new(pool::ConnectionPool::new(
+        Duration::from_secs(args.pool_idle_timeout),
+        args.pool_max_per_host,
+    )));
+
+    // 所有请求写入同一个 trace 文件
+    let trace = match (&args.trace, &args.trace_ascii) {
+        (Some(path), _) => Some((path, trace::TraceFormat::Hex)),
+        (None, Some(path)) => Some((path, trace::TraceFormat::Ascii)),
+        (None, None) => None,
     }
-    if !args.header.is_empty() {
-        for item in &args.header {
-            request.add_item_to_header(item);
+    .map(
+        |(path, format)| match trace::Trace::open(path, format, args.trace_time) {
+            Ok(trace) => Arc::new(Mutex::new(trace)),
+            Err(e) => {
+                // 与 curl 相同, 无法写入输出时退出码为 23
+                eprintln!("Error opening trace file {}: {}", path, e);
+                std::process::exit(23);
+            }
+        },
+    );
+
+    // -w @file 从文件读取模板
+    let write_out = args.write_out.as_ref().map(|w| match w.strip_prefix('@') {
+        Some(path) => file_io::read_file_to_string(path).unwrap_or_else(|e| {
+            // 与 curl 相同, 读取文件失败时退出码为 26
+            eprintln!("Error reading write-out file {}: {}", path, e);
+            std::process::exit(26);
+        }),
+        None => w.clone(),
+    });
+
+    let formdata = if args.formdata.is_empty() {
+        None
+    } else {
+        Some(requester::build_formdata(&args.formdata).unwrap())
+    };
+
+    for (i, url_str) in args.url.iter().enumerate() {
+        let (url, scope_id) = match parser::parse_url(url_str) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                eprintln!("Error parsing URL {}: {}", url_str, e);
+                continue;
+            }
+        };
+        let mut request = requester::Request::new(&url);
+        request.set_happy_eyeballs_timeout(Duration::from_millis(args.happy_eyeballs_timeout_ms));
+        request.set_resolver(args.resolver);
+        request.set_dns_cache_timeout(
+            u64::try_from(args.dns_cache_timeout)
+                .ok()
+                .map(Duration::from_secs),
+        );
+        if !args.dns_servers.is_empty() {
+            request.set_dns_servers(&args.dns_servers);
         }
-    }
-    
-    // 默认为GET
-    let method = args.method;
-    let scheme = url.scheme();
+        if let Some(doh_url) = &args.doh_url {
+            request.set_doh_url(doh_url.clone());
+        }
+        for entry in &args.resolve {
+            request.add_resolve(entry.clone());
+        }
+        for rule in &args.connect_to {
+            request.add_connect_to(rule.clone());
+        }
+        if let Some(scope_id) = scope_id {
+            request.set_scope_id(scope_id);
+        }
+        if args.ipv4 {
+            request.set_ip_family(parser::IpFamily::V4);
+        } else if args.ipv6 {
+            request.set_ip_family(parser::IpFamily::V6);
+        }
+        // 与 curl 相同, --socks* 优先于 -x
+        let proxy = args
+            .socks5_hostname
+            .as_ref()
+            .or(args.socks5.as_ref())
+            .or(args.socks4a.as_ref())
+            .or(args.proxy.as_ref());
+        if let Some(proxy) = proxy {
+            request.set_proxy(proxy.clone());
+            request.set_proxytunnel(args.proxytunnel);
+            if let Some(user) = &args.proxy_user {
+                request.set_proxy_user(user);
+            }
+        }
+        request.set_pool(pool.clone());
 
-    if scheme == "https" {
-        if let Err(e) = request.https_do(method) {
-            eprintln!("Error during HTTP request: {}", e);
-            return; // Exit if there's an error
+        if let Some(data) = &args.data {
+            request.set_data(data);
+        }
+        if let Some(output) = args.output.get(i) {
+            request.set_output(output);
+        }
+        if let Some(range) = &args.range {
+            if let Err(e) = request.set_range(range) {
+                eprintln!("Error during HTTP request: {}", e);
+                continue;
+            }
+        }
+        if let Some(offset) = &args.continue_at {
+            match resume_offset(offset, args.output.get(i)) {
+                Ok(offset) => {
+                    request.set_resume_from(offset);
+                }
+                Err(e) => {
+                    eprintln!("Error during HTTP request: {}", e);
+                    continue;
+                }
+            }
+        }
+        // 与 curl 一样, 输出到终端时默认不显示进度表
+        let progress = if args.silent {
+            None
+        } else if args.progress_bar {
+            Some(progress::ProgressStyle::Bar)
+        } else if args.output.get(i).is_some() || !std::io::stdout().is_terminal() {
+            Some(progress::ProgressStyle::Meter)
+        } else {
+            None
+        };
+        if let Some(style) = progress {
+            request.set_progress(style);
+        }
+        if let Some((boundary, body)) = &formdata {
+            request.set_formdata(body, boundary.clone());
+        }
+        // 放在 -H 之前, 允许用户覆盖 Accept-Encoding
+        if args.compressed {
+            request.set_compressed();
+        }
+        if args.include || args.head {
+            request.set_include_headers();
         }
-    } else if scheme == "http" {
-        if let Err(e) = request.http_do(method) {
+        if args.verbose {
+            request.set_verbose();
+        }
+        if let Some(trace) = &trace {
+            request.set_trace(trace.clone());
+        }
+        if args.trace_time {
+            request.set_trace_time();
+        }
+        if let Some(timeout) = args.connect_timeout {
+            request.set_connect_timeout(timeout);
+        }
+        if let Some(max_time) = args.max_time {
+            request.set_max_time(max_time);
+        }
+        request.set_retry(args.retry);
+        if let Some(delay) = args.retry_delay {
+            request.set_retry_delay(delay);
+        }
+        if let Some(max_time) = args.retry_max_time {
+            request.set_retry_max_time(max_time);
+        }
+        if args.retry_all_errors {
+            request.set_retry_all_errors();
+        }
+        if args.location {
+            request.set_follow_location(usize::try_from(args.max_redirs).ok());
+        }
+        if !args.header.is_empty() {
+            for item in &args.header {
+                request.add_item_to_header(item);
+            }
+        }
+
+        // 默认为GET, -I 使用 HEAD
+        let method = if args.head {
+            "HEAD".to_string()
+        } else {
+            args.method.clone()
+        };
+        if let Err(e) = request.perform(method) {
             eprintln!("Error during HTTP request: {}", e);
-            return; // Exit if there's an error
         }
-    } else {
-        println!("unsupported scheme: {}", scheme);
+        if let Some(template) = &write_out {
+            print!("{}", writeout::render(template, request.transfer_info()));
+        }
     }
+}
 
-    if let Some(output) = args.output {
-        if let Err(e) = request.response_output(&output) {
-            eprintln!("Error during write response to output: {}", e);
-            return;
-        }
+// -C <offset> 或 -C - (使用输出文件的大小)
+fn resume_offset(value: &str, output: Option<&String>) -> Result<u64, Box<dyn std::error::Error>> {
+    if value == "-" {
+        let output = output.ok_or("-C - requires an output file")?;
+        Ok(file_io::file_size(output)?)
+    } else {
+        Ok(value
+            .parse()
+            .map_err(|_| format!("invalid resume offset: {}", value))?)
     }
 }
+
+// 秒数, 可以是小数, 例如 2.5
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| format!("invalid number of seconds: {}", value))
+}
@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::io::{self, Read};
+
+use crate::response::{get_header, ContentRange, MAX_HEAD_SIZE};
+
+/// multipart/byteranges 响应中的一个分段
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BytePart {
+    pub headers: HashMap<String, String>,
+    pub content_range: Option<ContentRange>,
+    pub len: u64, // 分段数据的长度
+}
+
+/// 从 Content-Type 中取出 multipart/byteranges 的 boundary
+pub fn byteranges_boundary(content_type: &str) -> Option<String> {
+    let mut params = content_type.split(';');
+    if !params
+        .next()?
+        .trim()
+        .eq_ignore_ascii_case("multipart/byteranges")
+    {
+        return None;
+    }
+    params.find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case("boundary")
+            .then(|| v.trim().trim_matches('"').to_string())
+    })
+}
+
+enum State {
+    Preamble,      // 第一个 boundary 之前
+    AfterBoundary, // boundary 之后: "--" 表示结束, 否则是 CRLF
+    Headers,
+    Data,
+    Done,
+}
+
+/// 流式解析 multipart/byteranges, 只输出各分段的数据, 分段头部记录在 parts 中
+pub struct MultipartReader<R: Read> {
+    inner: R,
+    delimiter: Vec<u8>, // "\r\n--" + boundary
+    buf: Vec<u8>,
+    head_len: usize, // 当前分段 boundary 之后已读取的头部长度
+    state: State,
+    pub parts: Vec<BytePart>,
+}
+
+impl<R: Read> MultipartReader<R> {
+    pub fn new(inner: R, boundary: &str) -> Self {
+        MultipartReader {
+            inner,
+            delimiter: format!("\r\n--{}", boundary).into_bytes(),
+            buf: Vec::new(),
+            head_len: 0,
+            state: State::Preamble,
+            parts: Vec::new(),
+        }
+    }
+
+    /// 当前分段的 Content-Type
+    pub fn part_content_type(&self) -> Option<&str> {
+        get_header(&self.parts.last()?.headers, "Content-Type")
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        let mut buffer = [0u8; 8192];
+        let n = self.inner.read(&mut buffer)?;
+        if n == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "multipart body ended before the closing boundary",
+            ));
+        }
+        self.buf.extend_from_slice(&buffer[..n]);
+        Ok(())
+    }
+
+    /// 分段头部还没有读到换行, 没有超过长度限制时继续读取
+    fn fill_head(&mut self) -> io::Result<()> {
+        if self.head_len + self.buf.len() > MAX_HEAD_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "multipart part headers too large",
+            ));
+        }
+        self.fill()
+    }
+
+    fn find(&self, needle: &[u8]) -> Option<usize> {
+        self.buf.windows(needle.len()).position(|w| w == needle)
+    }
+}
+
+impl<R: Read> Read for MultipartReader<R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if out.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            match self.state {
+                State::Done => {
+                    // 丢弃结尾部分, 读到响应体末尾
+                    self.buf.clear();
+                    let mut buffer = [0u8; 8192];
+                    while self.inner.read(&mut buffer)? > 0 {}
+                    return Ok(0);
+                }
+                State::Preamble => {
+                    // 第一个 boundary 前面可以没有 CRLF
+                    let dash_boundary = self.delimiter[2..].to_vec();
+                    match self.find(&dash_boundary) {
+                        Some(i) => {
+                            self.buf.drain(..i + dash_boundary.len());
+                            self.head_len = 0;
+                            self.state = State::AfterBoundary;
+                        }
+                        None => {
+                            let keep = self.buf.len().min(dash_boundary.len());
+                            self.buf.drain(..self.buf.len() - keep);
+                            self.fill()?;
+                        }
+                    }
+                }
+                State::AfterBoundary => {
+                    if self.buf.len() < 2 {
+                        self.fill()?;
+                    } else if self.buf.starts_with(b"--") {
+                        self.state = State::Done;
+                    } else if let Some(i) = self.find(b"\n") {
+                        self.buf.drain(..=i);
+                        self.head_len += i + 1;
+                        self.parts.push(BytePart {
+                            headers: HashMap::new(),
+                            content_range: None,
+                            len: 0,
+                        });
+                        self.state = State::Headers;
+                    } else {
+                        self.fill_head()?;
+                    }
+                }
+                State::Headers => match self.find(b"\n") {
+                    Some(i) => {
+                        let line = String::from_utf8_lossy(&self.buf[..i]).trim().to_string();
+                        self.buf.drain(..=i);
+                        self.head_len += i + 1;
+                        let part = self.parts.last_mut().unwrap();
+                        if line.is_empty() {
+                            part.content_range = get_header(&part.headers, "Content-Range")
+                                .and_then(ContentRange::parse);
+                            self.state = State::Data;
+                        } else if let Some((k, v)) = line.split_once(':') {
+                            part.headers
+                                .insert(k.trim().to_string(), v.trim().to_string());
+                        }
+                    }
+                    None => self.fill_head()?,
+                },
+                State::Data => {
+                    let (n, found) = match self.find(&self.delimiter) {
+                        Some(i) => (i, true),
+                        // 末尾可能是 boundary 的一部分, 先保留
+                        None => (
+                            self.buf.len().saturating_sub(self.delimiter.len() - 1),
+                            false,
+                        ),
+                    };
+                    if n > 0 {
+                        let n = n.min(out.len());
+                        out[..n].copy_from_slice(&self.buf[..n]);
+                        self.buf.drain(..n);
+                        self.parts.last_mut().unwrap().len += n as u64;
+                        return Ok(n);
+                    }
+                    if found {
+                        self.buf.drain(..self.delimiter.len());
+                        self.head_len = 0;
+                        self.state = State::AfterBoundary;
+                    } else {
+                        self.fill()?;
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_multipart_byteranges() {
+    assert_eq!(
+        byteranges_boundary("multipart/byteranges; boundary=\"THIS_STRING\""),
+        Some("THIS_STRING".to_string())
+    );
+    assert_eq!(byteranges_boundary("multipart/form-data; boundary=x"), None);
+
+    let body = b"preamble\r\n--THIS_STRING\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-4/20\r\n\r\nhello\r\n--THIS_STRING\r\nContent-Range: bytes 10-14/20\r\n\r\nwo\r\nd\r\n--THIS_STRING--\r\nepilogue";
+    // 每次只给 3 个字节, 检查跨块的 boundary
+    struct Slow<'a>(&'a [u8]);
+    impl Read for Slow<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.0.len().min(3).min(buf.len());
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    let mut reader = MultipartReader::new(Slow(body), "THIS_STRING");
+    let mut data = Vec::new();
+    let mut buffer = [0u8; 4];
+    loop {
+        let n = reader.read(&mut buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        data.extend_from_slice(&buffer[..n]);
+    }
+    assert_eq!(data, b"hellowo\r\nd");
+    assert_eq!(reader.parts.len(), 2);
+    assert_eq!(reader.parts[0].content_range.unwrap().range, Some((0, 4)));
+    assert_eq!(reader.parts[1].content_range.unwrap().range, Some((10, 14)));
+    assert_eq!(reader.parts[1].len, 5);
+    assert_eq!(reader.part_content_type(), None);
+
+    // 缺少结束 boundary
+    let mut reader = MultipartReader::new(&b"--B\r\n\r\nabc"[..], "B");
+    assert!(io::copy(&mut reader, &mut io::sink()).is_err());
+
+    // 分段头部和 boundary 之后的行没有结束, 超过长度限制时报错
+    for (prefix, pad) in [(&b"--B\r\nX-Pad: "[..], b'y'), (&b"--B"[..], b' ')] {
+        let mut reader = MultipartReader::new(prefix.chain(io::repeat(pad)), "B");
+        let err = io::copy(&mut reader, &mut io::sink()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,66 +1,695 @@
+use std::collections::HashMap;
+use std::fmt;
 use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
-use std::net::SocketAddr;
+use std::net::{SocketAddr, ToSocketAddrs};
 use url::{Host, Url};
 
-
+use trust_dns_resolver::config::{
+    LookupIpStrategy, NameServerConfig, Protocol, ResolverConfig, ResolverOpts,
+};
+use trust_dns_resolver::system_conf;
 use trust_dns_resolver::Resolver;
-use trust_dns_resolver::config::{ResolverConfig, ResolverOpts};
 
-fn resolve_domain(domain: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
-    // 使用系统默认配置创建解析器
-    let resolver = Resolver::new(ResolverConfig::default(), ResolverOpts::default())?;
+use crate::doh;
+
+const HOSTS_FILE: &str = "/etc/hosts";
+
+/// -4/-6 限制使用的地址族
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum IpFamily {
+    #[default]
+    Any,
+    V4,
+    V6,
+}
+
+impl IpFamily {
+    fn allows(self, ip: &IpAddr) -> bool {
+        match self {
+            IpFamily::Any => true,
+            IpFamily::V4 => ip.is_ipv4(),
+            IpFamily::V6 => ip.is_ipv6(),
+        }
+    }
+}
+
+/// --resolve host:port:addr[,addr]..., host 为 * 时匹配任意 host
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ResolveOverride {
+    host: String,
+    port: u16,
+    addrs: Vec<IpAddr>,
+}
+
+impl ResolveOverride {
+    fn matches(&self, host: &str, port: u16) -> bool {
+        (self.host == "*" || self.host.eq_ignore_ascii_case(host)) && self.port == port
+    }
+}
+
+impl FromStr for ResolveOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let err = || {
+            format!(
+                "invalid --resolve '{}', expected host:port:addr[,addr]...",
+                s
+            )
+        };
+        let (host, rest) = s.split_once(':').ok_or_else(err)?;
+        let (port, addrs) = rest.split_once(':').ok_or_else(err)?;
+        if host.is_empty() {
+            return Err(err());
+        }
+        let addrs = addrs
+            .split(',')
+            .map(|a| unbracket(a.trim()).parse().map_err(|_| err()))
+            .collect::<Result<Vec<IpAddr>, String>>()?;
+        Ok(ResolveOverride {
+            host: host.to_string(),
+            port: port.parse().map_err(|_| err())?,
+            addrs,
+        })
+    }
+}
+
+/// --connect-to host:port:connhost:connport, 前两项为空时匹配任意 host/端口,
+/// 后两项为空时保持不变
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConnectTo {
+    host: Option<String>,
+    port: Option<u16>,
+    conn_host: Option<Host<String>>,
+    conn_port: Option<u16>,
+}
+
+impl ConnectTo {
+    /// 匹配时返回实际连接的 host 和端口
+    pub fn apply(&self, host: &Host<String>, port: u16) -> Option<(Host<String>, u16)> {
+        if self
+            .host
+            .as_ref()
+            .is_some_and(|h| !h.eq_ignore_ascii_case(&host.to_string()))
+            || self.port.is_some_and(|p| p != port)
+        {
+            return None;
+        }
+        Some((
+            self.conn_host.clone().unwrap_or_else(|| host.clone()),
+            self.conn_port.unwrap_or(port),
+        ))
+    }
+}
+
+impl FromStr for ConnectTo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let err = || {
+            format!(
+                "invalid --connect-to '{}', expected host:port:connhost:connport",
+                s
+            )
+        };
+        let (host, rest) = s.split_once(':').ok_or_else(err)?;
+        let (port, rest) = rest.split_once(':').ok_or_else(err)?;
+        // connhost 可以是带方括号的 IPv6 地址
+        let (conn_host, conn_port) = rest.rsplit_once(':').ok_or_else(err)?;
+        let parse_port = |p: &str| -> Result<Option<u16>, String> {
+            match p {
+                "" => Ok(None),
+                p => p.parse().map(Some).map_err(|_| err()),
+            }
+        };
+        Ok(ConnectTo {
+            host: Some(host.to_string()).filter(|h| !h.is_empty()),
+            port: parse_port(port)?,
+            conn_host: match conn_host {
+                "" => None,
+                h => Some(Host::parse(h).map_err(|_| err())?),
+            },
+            conn_port: parse_port(conn_port)?,
+        })
+    }
+}
+
+/// --resolver: builtin 使用 trust-dns, 读取 /etc/hosts 和 /etc/resolv.conf;
+/// system 使用系统的 getaddrinfo
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ResolverKind {
+    #[default]
+    Builtin,
+    System,
+}
+
+impl FromStr for ResolverKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "builtin" => Ok(ResolverKind::Builtin),
+            "system" => Ok(ResolverKind::System),
+            _ => Err(format!(
+                "unknown resolver '{}', expected system or builtin",
+                s
+            )),
+        }
+    }
+}
+
+/// 域名解析的配置
+#[derive(Clone, Debug)]
+pub struct ResolveOptions {
+    pub family: IpFamily,
+    pub overrides: Vec<ResolveOverride>,
+    pub resolver: ResolverKind,
+    pub dns_servers: Vec<SocketAddr>, // --dns-servers, 代替 /etc/resolv.conf 中的 nameserver
+    pub doh_url: Option<Url>,         // --doh-url
+    pub cache_timeout: Option<Duration>, // --dns-cache-timeout, None 表示只按 TTL 过期
+}
+
+impl Default for ResolveOptions {
+    fn default() -> Self {
+        ResolveOptions {
+            family: IpFamily::Any,
+            overrides: Vec::new(),
+            resolver: ResolverKind::Builtin,
+            dns_servers: Vec::new(),
+            doh_url: None,
+            cache_timeout: Some(DNS_CACHE_TIMEOUT),
+        }
+    }
+}
+
+/// 与 curl 相同, 解析结果默认最多缓存 60 秒
+pub const DNS_CACHE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// DNS 缓存的键: 同一个域名在不同的解析配置下结果可能不同
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct CacheKey {
+    domain: String,
+    family: IpFamily,
+    resolver: ResolverKind,
+    dns_servers: Vec<SocketAddr>,
+    doh_url: Option<String>,
+}
+
+impl CacheKey {
+    fn new(domain: &str, opts: &ResolveOptions) -> Self {
+        CacheKey {
+            domain: domain.trim_end_matches('.').to_ascii_lowercase(),
+            family: opts.family,
+            resolver: opts.resolver,
+            dns_servers: opts.dns_servers.clone(),
+            doh_url: opts.doh_url.as_ref().map(Url::to_string),
+        }
+    }
+}
+
+struct CacheEntry {
+    ips: Vec<IpAddr>,
+    expires: Option<Instant>, // None 表示不过期
+}
+
+/// 进程内共享的 DNS 缓存, 多个 URL, 重定向和重试都不再重复解析
+fn dns_cache() -> &'static Mutex<HashMap<CacheKey, CacheEntry>> {
+    static CACHE: OnceLock<Mutex<HashMap<CacheKey, CacheEntry>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn cache_get(key: &CacheKey) -> Option<Vec<IpAddr>> {
+    let mut cache = dns_cache().lock().ok()?;
+    let entry = cache.get(key)?;
+    if entry.expires.is_some_and(|t| Instant::now() >= t) {
+        cache.remove(key);
+        return None;
+    }
+    Some(entry.ips.clone())
+}
+
+fn cache_put(key: CacheKey, ips: &[IpAddr], ttl: Option<Duration>, timeout: Option<Duration>) {
+    let Some(lifetime) = cache_lifetime(ttl, timeout) else {
+        return;
+    };
+    if let Ok(mut cache) = dns_cache().lock() {
+        let expires = lifetime.and_then(|t| Instant::now().checked_add(t));
+        cache.insert(
+            key,
+            CacheEntry {
+                ips: ips.to_vec(),
+                expires,
+            },
+        );
+    }
+}
+
+/// 缓存时间取记录的 TTL 和 --dns-cache-timeout 中较小的一个.
+/// 返回 None 表示不缓存, Some(None) 表示不过期
+fn cache_lifetime(ttl: Option<Duration>, timeout: Option<Duration>) -> Option<Option<Duration>> {
+    let lifetime = match (ttl, timeout) {
+        (Some(ttl), Some(timeout)) => Some(ttl.min(timeout)),
+        (ttl, timeout) => ttl.or(timeout),
+    };
+    match lifetime {
+        Some(t) if t.is_zero() => None,
+        lifetime => Some(lifetime),
+    }
+}
+
+/// --dns-servers 中的一项: ip, ip:port 或 [ipv6]:port
+pub fn parse_dns_server(s: &str) -> Result<SocketAddr, String> {
+    s.parse()
+        .or_else(|_| unbracket(s).parse().map(|ip| SocketAddr::new(ip, 53)))
+        .map_err(|_| format!("invalid DNS server '{}'", s))
+}
+
+/// 域名解析失败, --retry 时作为暂时性错误重试
+#[derive(Debug)]
+pub struct ResolveError(String);
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to resolve domain: {}", self.0)
+    }
+}
+
+impl std::error::Error for ResolveError {}
+
+fn resolve_domain(
+    domain: &str,
+    opts: &ResolveOptions,
+) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    if opts.doh_url.is_none() && opts.resolver == ResolverKind::Builtin {
+        let hosts = std::fs::read_to_string(HOSTS_FILE).unwrap_or_default();
+        let ips: Vec<IpAddr> = hosts_lookup(&hosts, domain)
+            .into_iter()
+            .filter(|ip| opts.family.allows(ip))
+            .collect();
+        if !ips.is_empty() {
+            return Ok(ips);
+        }
+    }
+
+    let key = CacheKey::new(domain, opts);
+    if let Some(ips) = cache_get(&key) {
+        return Ok(ips);
+    }
+    // 系统解析得不到 TTL, 只按 --dns-cache-timeout 过期
+    let (ips, ttl) = match (&opts.doh_url, opts.resolver) {
+        (Some(doh_url), _) => doh::lookup(doh_url, domain, opts)?,
+        (None, ResolverKind::Builtin) => dns_lookup(domain, opts)?,
+        (None, ResolverKind::System) => (system_lookup(domain)?, None),
+    };
+    cache_put(key, &ips, ttl, opts.cache_timeout);
+    Ok(ips)
+}
+
+fn system_lookup(domain: &str) -> Result<Vec<IpAddr>, Box<dyn std::error::Error>> {
+    let ips: Vec<IpAddr> = (domain, 0).to_socket_addrs()?.map(|a| a.ip()).collect();
+    if ips.is_empty() {
+        return Err(format!("no address for {}", domain).into());
+    }
+    Ok(ips)
+}
+
+/// 返回解析到的地址和剩余的 TTL
+fn dns_lookup(
+    domain: &str,
+    options: &ResolveOptions,
+) -> Result<(Vec<IpAddr>, Option<Duration>), Box<dyn std::error::Error>> {
+    let Some(resolver) =
+        shared_resolver(&options.dns_servers, options.family, options.cache_timeout)?
+    else {
+        return Ok((system_lookup(domain)?, None));
+    };
     let response = resolver.lookup_ip(domain)?;
     let ips: Vec<IpAddr> = response.iter().collect();
-    Ok(ips)
+    let ttl = response
+        .valid_until()
+        .saturating_duration_since(Instant::now());
+    Ok((ips, Some(ttl)))
 }
 
-// url_str -> [ip:port, ...]
-pub fn to_adders(url: &Url) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error>> {
-    let mut res = vec![];
+/// 相同配置的解析器在进程内共享, 避免每次解析都重新读取配置和创建运行时.
+/// 读取不到 /etc/resolv.conf 又没有 --dns-servers 时返回 None, 改用系统解析
+fn shared_resolver(
+    dns_servers: &[SocketAddr],
+    family: IpFamily,
+    cache_timeout: Option<Duration>,
+) -> Result<Option<Arc<Resolver>>, Box<dyn std::error::Error>> {
+    type Resolvers =
+        Mutex<HashMap<(Vec<SocketAddr>, IpFamily, Option<Duration>), Option<Arc<Resolver>>>>;
+    static RESOLVERS: OnceLock<Resolvers> = OnceLock::new();
+    let mut resolvers = RESOLVERS
+        .get_or_init(Default::default)
+        .lock()
+        .map_err(|_| "resolver lock poisoned")?;
+    let key = (dns_servers.to_vec(), family, cache_timeout);
+    if let Some(resolver) = resolvers.get(&key) {
+        return Ok(resolver.clone());
+    }
 
-    match url.host() {
-        Some(Host::Domain(domain)) => {
+    // 使用 /etc/resolv.conf 中的 nameserver, search 和 ndots
+    let (mut config, opts) = match system_conf::read_system_conf() {
+        Ok(conf) => conf,
+        Err(_) if !dns_servers.is_empty() => (ResolverConfig::new(), ResolverOpts::default()),
+        Err(e) => {
+            eprintln!(
+                "Warning: cannot read /etc/resolv.conf ({}), using the system resolver",
+                e
+            );
+            resolvers.insert(key, None);
+            return Ok(None);
+        }
+    };
+    if !dns_servers.is_empty() {
+        // 与 resolv.conf 一样, 同时使用 UDP 和 TCP
+        let servers: Vec<NameServerConfig> = dns_servers
+            .iter()
+            .flat_map(|&addr| {
+                [Protocol::Udp, Protocol::Tcp].map(|protocol| NameServerConfig {
+                    socket_addr: addr,
+                    protocol,
+                    tls_dns_name: None,
+                    trust_nx_responses: true,
+                })
+            })
+            .collect();
+        config =
+            ResolverConfig::from_parts(config.domain().cloned(), config.search().to_vec(), servers);
+    }
+    let opts = ResolverOpts {
+        ip_strategy: match family {
+            IpFamily::Any => LookupIpStrategy::Ipv4AndIpv6,
+            IpFamily::V4 => LookupIpStrategy::Ipv4Only,
+            IpFamily::V6 => LookupIpStrategy::Ipv6Only,
+        },
+        use_hosts_file: false, // 已经在 resolve_domain 中查过
+        // 解析器内部的缓存也不超过 --dns-cache-timeout
+        positive_max_ttl: cache_timeout,
+        negative_max_ttl: cache_timeout,
+        ..opts
+    };
+    let resolver = Arc::new(Resolver::new(config, opts)?);
+    resolvers.insert(key, Some(resolver.clone()));
+    Ok(Some(resolver))
+}
+
+/// 在 hosts 文件内容中查找 name 对应的地址
+fn hosts_lookup(hosts: &str, name: &str) -> Vec<IpAddr> {
+    let name = name.trim_end_matches('.');
+    let mut ips = Vec::new();
+    for line in hosts.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        let mut fields = line.split_whitespace();
+        let Some(Ok(ip)) = fields.next().map(str::parse::<IpAddr>) else {
+            continue;
+        };
+        if fields.any(|n| n.eq_ignore_ascii_case(name)) && !ips.contains(&ip) {
+            ips.push(ip);
+        }
+    }
+    ips
+}
+
+// host:port -> [ip:port, ...], 错误可以跨线程传递
+pub fn to_adders(
+    host: &Host<String>,
+    port: u16,
+    opts: &ResolveOptions,
+) -> Result<Vec<SocketAddr>, Box<dyn std::error::Error + Send + Sync>> {
+    let family = opts.family;
+    let ips = match host {
+        // --resolve 指定的地址不经过 dns 解析
+        Host::Domain(domain) => match opts.overrides.iter().find(|o| o.matches(domain, port)) {
+            Some(o) => o.addrs.clone(),
             // dns 解析
-            match resolve_domain(domain) {
+            None => match resolve_domain(domain, opts) {
                 Ok(ips) => {
                     assert!(!ips.is_empty(), "Should resolve to at least one IP");
-
-                    let port;
-                    if let Some(p) = url.port() {
-                        port = p;
-                    } else {
-                        if url.scheme() == "http"{
-                        port = 80;
-                        } else if url.scheme() == "https"
-                        {
-                            port = 443;
-                        }else
-                        {
-                            return Err(format!("unsupported scheme").into());
-                        }
-                    }
-                    for ipi in &ips {
-                        res.push(SocketAddr::new(*ipi, port));
-                    }
-                    return Ok(res);
+                    ips
                 }
-                Err(e) => return Err(format!("Failed to resolve domain: {}", e).into()),
-            }
-        }
-        Some(Host::Ipv6(_)) => return Err("not support ipv6".into()),
+                Err(e) => return Err(ResolveError(e.to_string()).into()),
+            },
+        },
         // 明文ip
-        Some(Host::Ipv4(ips)) => {
-            let port;
-            if let Some(p) = url.port() {
-                port = p;
-            } else {
-                port = 80;
-            }
-            res.push(SocketAddr::new(IpAddr::V4(ips), port));
-            return Ok(res);
+        Host::Ipv6(ip) => vec![IpAddr::V6(*ip)],
+        Host::Ipv4(ip) => vec![IpAddr::V4(*ip)],
+    };
+    let ips: Vec<IpAddr> = ips.into_iter().filter(|ip| family.allows(ip)).collect();
+    if ips.is_empty() {
+        let kind = match family {
+            IpFamily::V4 => "IPv4",
+            IpFamily::V6 => "IPv6",
+            IpFamily::Any => "usable",
+        };
+        return Err(format!("no {} address for {}", kind, host).into());
+    }
+    Ok(sort_addrs(ips)
+        .into_iter()
+        .map(|ip| SocketAddr::new(ip, port))
+        .collect())
+}
+
+/// 地址排序: IPv6 优先, 两个地址族交替排列 (RFC 8305), 一个地址族不可用时很快尝试另一个
+fn sort_addrs(ips: Vec<IpAddr>) -> Vec<IpAddr> {
+    let (mut v6, mut v4): (Vec<IpAddr>, Vec<IpAddr>) = ips.into_iter().partition(IpAddr::is_ipv6);
+    v6.dedup();
+    v4.dedup();
+    let mut sorted = Vec::with_capacity(v6.len() + v4.len());
+    let (mut v6, mut v4) = (v6.into_iter(), v4.into_iter());
+    loop {
+        match (v6.next(), v4.next()) {
+            (None, None) => return sorted,
+            (a, b) => sorted.extend(a.into_iter().chain(b)),
         }
-        None => return Err("miss host name".into()),
     }
 }
 
+/// 不带方括号的 host, 用于 TLS SNI 和日志
+pub fn bare_host(url: &Url) -> Option<&str> {
+    Some(unbracket(url.host_str()?))
+}
+
+fn unbracket(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|h| h.strip_suffix(']'))
+        .unwrap_or(host)
+}
+
+/// 解析 URL, 支持 IPv6 link-local 地址的 zone ID (RFC 6874), 例如 `http://[fe80::1%25eth0]/`.
+/// url 库不接受 zone ID, 解析前从 host 中去掉, 返回对应的网卡序号
+pub fn parse_url(input: &str) -> Result<(Url, Option<u32>), Box<dyn std::error::Error>> {
+    let zone = input.find("://[").and_then(|start| {
+        let host_start = start + 4;
+        let host_end = host_start + input[host_start..].find(']')?;
+        let pct = host_start + input[host_start..host_end].find('%')?;
+        Some((pct, host_end))
+    });
+    let Some((pct, host_end)) = zone else {
+        return Ok((Url::parse(input)?, None));
+    };
+    let raw = &input[pct + 1..host_end];
+    let name = raw
+        .strip_prefix("25")
+        .filter(|n| !n.is_empty())
+        .unwrap_or(raw);
+    let scope_id = scope_id(name).ok_or_else(|| format!("invalid IPv6 zone id: {}", name))?;
+    let url = Url::parse(&format!("{}{}", &input[..pct], &input[host_end..]))?;
+    Ok((url, Some(scope_id)))
+}
+
+/// zone ID 可以是网卡序号或网卡名
+fn scope_id(zone: &str) -> Option<u32> {
+    if let Ok(index) = zone.parse() {
+        return Some(index);
+    }
+    if zone.is_empty() || zone.contains('/') || zone.starts_with('.') {
+        return None;
+    }
+    let index = std::fs::read_to_string(format!("/sys/class/net/{}/ifindex", zone)).ok()?;
+    index.trim().parse().ok()
+}
+
+#[test]
+fn test_ipv6_url() {
+    let (url, zone) = parse_url("http://[fe80::1%251]:8080/a").unwrap();
+    assert_eq!(url.as_str(), "http://[fe80::1]:8080/a");
+    assert_eq!(zone, Some(1));
+    assert_eq!(parse_url("http://[fe80::1%2]/").unwrap().1, Some(2));
+    assert!(parse_url("http://[fe80::1%25no/such]/").is_err());
+    let (url, zone) = parse_url("http://[::1]/").unwrap();
+    assert_eq!((bare_host(&url), zone), (Some("::1"), None));
+
+    let host = url.host().unwrap().to_owned();
+    let mut opts = ResolveOptions::default();
+    assert_eq!(
+        to_adders(&host, 80, &opts).unwrap(),
+        vec!["[::1]:80".parse().unwrap()]
+    );
+    opts.family = IpFamily::V4;
+    assert!(to_adders(&host, 80, &opts).is_err());
+
+    let ips: Vec<IpAddr> = ["1.1.1.1", "2.2.2.2", "::1", "::2", "::3"]
+        .iter()
+        .map(|ip| ip.parse().unwrap())
+        .collect();
+    let sorted: Vec<String> = sort_addrs(ips).iter().map(IpAddr::to_string).collect();
+    assert_eq!(sorted, ["::1", "1.1.1.1", "::2", "2.2.2.2", "::3"]);
+}
+
+#[test]
+fn test_resolve_overrides() {
+    let o: ResolveOverride = "example.com:443:127.0.0.1,[::1]".parse().unwrap();
+    assert_eq!(
+        o.addrs,
+        [
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "::1".parse().unwrap()
+        ]
+    );
+    assert!(o.matches("EXAMPLE.com", 443) && !o.matches("example.com", 80));
+    assert!("*:80:10.0.0.1"
+        .parse::<ResolveOverride>()
+        .unwrap()
+        .matches("a.b", 80));
+    assert!("example.com:443".parse::<ResolveOverride>().is_err());
+    assert!("example.com:443:nope".parse::<ResolveOverride>().is_err());
+
+    // 不经过 dns 解析, IPv6 优先
+    let opts = ResolveOptions {
+        overrides: vec![o],
+        ..ResolveOptions::default()
+    };
+    let host = Host::Domain("example.com".to_string());
+    assert_eq!(
+        to_adders(&host, 443, &opts).unwrap(),
+        vec![
+            "[::1]:443".parse().unwrap(),
+            "127.0.0.1:443".parse().unwrap()
+        ]
+    );
+
+    let rule: ConnectTo = "example.com:443:[::1]:8443".parse().unwrap();
+    let target = rule.apply(&host, 443).unwrap();
+    assert_eq!(target, (Host::parse("[::1]").unwrap(), 8443));
+    assert_eq!(rule.apply(&host, 80), None);
+    assert_eq!(rule.apply(&Host::Domain("other".to_string()), 443), None);
+
+    // 空字段: 匹配任意 host, 只改端口
+    let rule: ConnectTo = ":::8080".parse().unwrap();
+    assert_eq!(rule.apply(&host, 80), Some((host.clone(), 8080)));
+    assert!("a:b:c:d".parse::<ConnectTo>().is_err());
+}
+
+#[test]
+fn test_hosts_lookup() {
+    let hosts = "# comment\n127.0.0.1 localhost vm\n::1\tlocalhost ip6-localhost # loopback\n\nbad line\n10.0.0.2 Staging.Example.com\n127.0.0.1 localhost\n";
+    assert_eq!(
+        hosts_lookup(hosts, "localhost"),
+        [
+            "127.0.0.1".parse::<IpAddr>().unwrap(),
+            "::1".parse().unwrap()
+        ]
+    );
+    assert_eq!(
+        hosts_lookup(hosts, "staging.example.com."),
+        ["10.0.0.2".parse::<IpAddr>().unwrap()]
+    );
+    assert!(hosts_lookup(hosts, "comment").is_empty());
+    assert!(hosts_lookup(hosts, "line").is_empty());
+    assert_eq!("system".parse(), Ok(ResolverKind::System));
+    assert!("dns".parse::<ResolverKind>().is_err());
+}
+
+#[test]
+fn test_dns_servers() {
+    use std::net::UdpSocket;
+
+    // 本地的 DNS 服务器, 只应答 A 记录
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    std::thread::spawn(move || {
+        let ip: IpAddr = "10.9.8.7".parse().unwrap();
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+            let _ = socket.send_to(&doh::stub_reply(&buf[..n], &[ip]), peer);
+        }
+    });
+
+    assert_eq!(parse_dns_server(&server_addr.to_string()), Ok(server_addr));
+    assert_eq!(
+        parse_dns_server("10.0.0.2"),
+        Ok("10.0.0.2:53".parse().unwrap())
+    );
+    assert_eq!(parse_dns_server("[::1]"), Ok("[::1]:53".parse().unwrap()));
+    assert!(parse_dns_server("nameserver").is_err());
+
+    let opts = ResolveOptions {
+        family: IpFamily::V4,
+        dns_servers: vec![server_addr],
+        ..ResolveOptions::default()
+    };
+    let host = Host::Domain("stub.test".to_string());
+    assert_eq!(
+        to_adders(&host, 80, &opts).unwrap(),
+        vec!["10.9.8.7:80".parse().unwrap()]
+    );
+}
+
+#[test]
+fn test_dns_cache() {
+    use std::net::UdpSocket;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let server_addr = socket.local_addr().unwrap();
+    let queries = Arc::new(AtomicUsize::new(0));
+    let counter = queries.clone();
+    std::thread::spawn(move || {
+        let ip: IpAddr = "10.9.8.6".parse().unwrap();
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = socket.recv_from(&mut buf) {
+            counter.fetch_add(1, Ordering::SeqCst);
+            let _ = socket.send_to(&doh::stub_reply(&buf[..n], &[ip]), peer);
+        }
+    });
+
+    let mut opts = ResolveOptions {
+        family: IpFamily::V4,
+        dns_servers: vec![server_addr],
+        ..ResolveOptions::default()
+    };
+    let host = Host::Domain("cached.test".to_string());
+    for _ in 0..3 {
+        assert_eq!(
+            to_adders(&host, 80, &opts).unwrap(),
+            vec!["10.9.8.6:80".parse().unwrap()]
+        );
+    }
+    assert_eq!(queries.load(Ordering::SeqCst), 1);
+
+    // --dns-cache-timeout 0: 不缓存
+    opts.cache_timeout = Some(Duration::ZERO);
+    let host = Host::Domain("uncached.test".to_string());
+    to_adders(&host, 80, &opts).unwrap();
+    to_adders(&host, 80, &opts).unwrap();
+    assert_eq!(queries.load(Ordering::SeqCst), 3);
+
+    let secs = Duration::from_secs;
+    assert_eq!(
+        cache_lifetime(Some(secs(300)), Some(secs(60))),
+        Some(Some(secs(60)))
+    );
+    assert_eq!(
+        cache_lifetime(Some(secs(5)), Some(secs(60))),
+        Some(Some(secs(5)))
+    );
+    assert_eq!(cache_lifetime(None, None), Some(None));
+    assert_eq!(cache_lifetime(Some(secs(0)), None), None);
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::{Duration, Instant};
+
+use rustls::{ClientConnection, StreamOwned};
+use url::Url;
+
+use crate::proxy::Proxy;
+
+/// 一条到服务器的连接, 明文 TCP 或 rustls
+pub enum Connection {
+    Plain(TcpStream),
+    Tls(Box<StreamOwned<ClientConnection, TcpStream>>),
+}
+
+impl Connection {
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp().peer_addr()
+    }
+
+    /// 设置底层 socket 的读写超时, None 表示一直阻塞
+    pub fn set_io_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        self.tcp().set_read_timeout(timeout)?;
+        self.tcp().set_write_timeout(timeout)
+    }
+
+    fn tcp(&self) -> &TcpStream {
+        match self {
+            Connection::Plain(s) => s,
+            Connection::Tls(s) => &s.sock,
+        }
+    }
+}
+
+impl Read for Connection {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(s) => s.read(buf),
+            Connection::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Connection {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Connection::Plain(s) => s.write(buf),
+            Connection::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Connection::Plain(s) => s.flush(),
+            Connection::Tls(s) => s.flush(),
+        }
+    }
+}
+
+/// 连接池的键: scheme + host + port + 代理
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PoolKey {
+    scheme: String,
+    host: String,
+    port: u16,
+    proxy: Option<String>,
+}
+
+impl PoolKey {
+    pub fn from_url(url: &Url) -> Option<Self> {
+        Some(PoolKey {
+            scheme: url.scheme().to_string(),
+            host: url.host_str()?.to_ascii_lowercase(),
+            port: url.port_or_known_default()?,
+            proxy: None,
+        })
+    }
+
+    /// 经过代理的连接不能给直连的请求使用
+    pub fn via(self, proxy: &Proxy) -> Self {
+        PoolKey {
+            proxy: Some(proxy.to_string()),
+            ..self
+        }
+    }
+}
+
+struct IdleConnection {
+    conn: Connection,
+    idle_since: Instant,
+}
+
+/// 按 host 复用 HTTP/1.1 keep-alive 连接
+pub struct ConnectionPool {
+    idle: HashMap<PoolKey, Vec<IdleConnection>>,
+    idle_timeout: Duration,
+    max_per_host: usize,
+}
+
+impl ConnectionPool {
+    /// idle_timeout: 空闲连接的最长保留时间
+    /// max_per_host: 每个 host 最多保留的空闲连接数
+    pub fn new(idle_timeout: Duration, max_per_host: usize) -> Self {
+        ConnectionPool {
+            idle: HashMap::new(),
+            idle_timeout,
+            max_per_host,
+        }
+    }
+
+    /// 取出一条空闲连接, 优先使用最近放回的, 超时的连接直接丢弃
+    pub fn take(&mut self, key: &PoolKey) -> Option<Connection> {
+        let conns = self.idle.get_mut(key)?;
+        let now = Instant::now();
+        conns.retain(|c| now.duration_since(c.idle_since) < self.idle_timeout);
+        conns.pop().map(|c| c.conn)
+    }
+
+    /// 放回一条可复用的连接, 超过上限时丢弃最早空闲的连接
+    pub fn put(&mut self, key: PoolKey, conn: Connection) {
+        if self.max_per_host == 0 {
+            return;
+        }
+        let conns = self.idle.entry(key).or_default();
+        if conns.len() >= self.max_per_host {
+            conns.remove(0);
+        }
+        conns.push(IdleConnection {
+            conn,
+            idle_since: Instant::now(),
+        });
+    }
+}
+
+#[test]
+fn test_pool_limits() {
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let url = Url::parse(&format!("http://{}/", addr)).unwrap();
+    let key = PoolKey::from_url(&url).unwrap();
+    let connect = || Connection::Plain(TcpStream::connect(addr).unwrap());
+    let idle_count = |pool: &ConnectionPool| pool.idle.get(&key).map(Vec::len).unwrap_or(0);
+
+    let mut pool = ConnectionPool::new(Duration::from_secs(60), 2);
+    for _ in 0..3 {
+        pool.put(key.clone(), connect());
+    }
+    assert_eq!(idle_count(&pool), 2);
+    assert!(pool.take(&key).is_some());
+    assert_eq!(idle_count(&pool), 1);
+
+    let other = PoolKey::from_url(&Url::parse("https://example.com/").unwrap()).unwrap();
+    assert!(pool.take(&other).is_none());
+
+    let mut pool = ConnectionPool::new(Duration::ZERO, 2);
+    pool.put(key.clone(), connect());
+    assert!(pool.take(&key).is_none());
+}
@@ -0,0 +1,326 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+/// 进度的显示方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressStyle {
+    Meter, // curl 默认的统计表格
+    Bar,   // -# 进度条
+}
+
+const REDRAW_INTERVAL: Duration = Duration::from_millis(500);
+// 当前速度按最近 5 秒的传输量计算
+const SPEED_WINDOW: Duration = Duration::from_secs(5);
+
+const METER_HEADER: &str =
+    "  % Total    % Received % Xferd  Average Speed   Time    Time     Time  Current
+                                 Dload  Upload   Total   Spent    Left  Speed";
+
+/// 在 stderr 上显示一次传输的上传/下载进度
+pub struct Progress {
+    style: ProgressStyle,
+    start: Instant,
+    last_draw: Option<Instant>,
+    header_shown: bool,
+    active: bool,
+    dl_total: Option<u64>,
+    dl_now: u64,
+    ul_total: Option<u64>,
+    ul_now: u64,
+    samples: VecDeque<(Instant, u64)>, // (时间, 已传输字节数)
+}
+
+impl Progress {
+    pub fn new(style: ProgressStyle) -> Self {
+        Progress {
+            style,
+            start: Instant::now(),
+            last_draw: None,
+            header_shown: false,
+            active: false,
+            dl_total: None,
+            dl_now: 0,
+            ul_total: None,
+            ul_now: 0,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// 开始一次新的传输, upload_total 为请求体的大小
+    pub fn start_transfer(&mut self, upload_total: Option<u64>) {
+        self.start = Instant::now();
+        self.last_draw = None;
+        self.active = true;
+        self.dl_total = None;
+        self.dl_now = 0;
+        self.ul_total = upload_total;
+        self.ul_now = 0;
+        self.samples.clear();
+        self.samples.push_back((self.start, 0));
+    }
+
+    /// 响应体的大小, 未知时为 None
+    pub fn set_download_total(&mut self, total: Option<u64>) {
+        self.dl_total = total;
+    }
+
+    pub fn upload(&mut self, n: usize) {
+        self.ul_now += n as u64;
+        self.tick();
+    }
+
+    pub fn download(&mut self, n: usize) {
+        self.dl_now += n as u64;
+        self.tick();
+    }
+
+    /// 输出最终的进度并换行
+    pub fn finish(&mut self) {
+        if !self.active {
+            return;
+        }
+        self.active = false;
+        self.draw(Instant::now());
+        eprintln!();
+    }
+
+    fn tick(&mut self) {
+        let now = Instant::now();
+        if self
+            .last_draw
+            .is_some_and(|last| now.duration_since(last) < REDRAW_INTERVAL)
+        {
+            return;
+        }
+        self.draw(now);
+    }
+
+    fn draw(&mut self, now: Instant) {
+        self.last_draw = Some(now);
+        self.samples.push_back((now, self.dl_now + self.ul_now));
+        while self
+            .samples
+            .front()
+            .is_some_and(|(t, _)| now.duration_since(*t) > SPEED_WINDOW)
+        {
+            self.samples.pop_front();
+        }
+
+        let mut stderr = io::stderr().lock();
+        let line = match self.style {
+            ProgressStyle::Meter => {
+                if !self.header_shown {
+                    self.header_shown = true;
+                    let _ = writeln!(stderr, "{}", METER_HEADER);
+                }
+                self.render_meter(now)
+            }
+            ProgressStyle::Bar => self.render_bar(now, terminal_width()),
+        };
+        let _ = write!(stderr, "\r{}", line);
+        let _ = stderr.flush();
+    }
+
+    /// 上传和下载的总量, 有一方未知时为 None
+    fn total(&self) -> Option<u64> {
+        match (self.dl_total, self.ul_total) {
+            (Some(dl), Some(ul)) => Some(dl + ul),
+            (Some(dl), None) if self.ul_now == 0 => Some(dl),
+            (None, Some(ul)) if self.dl_now == 0 => Some(ul),
+            _ => None,
+        }
+    }
+
+    fn render_meter(&self, now: Instant) -> String {
+        let spent = now.duration_since(self.start).as_secs_f64();
+        let dl_speed = speed(self.dl_now, spent);
+        let ul_speed = speed(self.ul_now, spent);
+
+        // 按平均速度估计上传和下载各自需要的时间, 取较长的一个
+        let estimate = |total: Option<u64>, speed: u64| match total {
+            Some(0) => Some(0.0),
+            Some(total) if speed > 0 => Some(total as f64 / speed as f64),
+            _ => None,
+        };
+        let time_total = match (self.dl_total, self.ul_total) {
+            (None, None) => None,
+            (dl, ul) => {
+                let dl = if dl.is_some() {
+                    estimate(dl, dl_speed)
+                } else {
+                    Some(0.0)
+                };
+                let ul = if ul.is_some() {
+                    estimate(ul, ul_speed)
+                } else {
+                    Some(0.0)
+                };
+                dl.zip(ul).map(|(dl, ul)| dl.max(ul))
+            }
+        };
+        let time_left = time_total.map(|t| (t - spent).max(0.0));
+
+        format!(
+            "{:3} {}  {:3} {}  {:3} {}  {}  {} {} {} {} {}",
+            percent(self.dl_now + self.ul_now, self.total()),
+            format_size(self.total().unwrap_or(0)),
+            percent(self.dl_now, self.dl_total),
+            format_size(self.dl_now),
+            percent(self.ul_now, self.ul_total),
+            format_size(self.ul_now),
+            format_size(dl_speed),
+            format_size(ul_speed),
+            format_time(time_total),
+            format_time(Some(spent)),
+            format_time(time_left),
+            format_size(self.current_speed(spent)),
+        )
+    }
+
+    fn render_bar(&self, now: Instant, width: usize) -> String {
+        // 进度条后面是 " 100.0%"
+        let bar_width = width.saturating_sub(8).max(10);
+        match self.total() {
+            Some(total) => {
+                let done = self.dl_now + self.ul_now;
+                let fraction = if total == 0 {
+                    1.0
+                } else {
+                    (done as f64 / total as f64).min(1.0)
+                };
+                let hashes = "#".repeat((fraction * bar_width as f64) as usize);
+                format!("{:<bar_width$} {:5.1}%", hashes, fraction * 100.0)
+            }
+            None => {
+                // 大小未知时来回移动的标记
+                let span = bar_width - 5;
+                let step = (now.duration_since(self.start).as_millis() / 100) as usize % (2 * span);
+                let pos = if step < span { step } else { 2 * span - step };
+                format!("{:<bar_width$}", format!("{}#=#=#", " ".repeat(pos)))
+            }
+        }
+    }
+
+    fn current_speed(&self, spent: f64) -> u64 {
+        match (self.samples.front(), self.samples.back()) {
+            (Some((t0, b0)), Some((t1, b1))) if t1 > t0 => {
+                speed(b1 - b0, t1.duration_since(*t0).as_secs_f64())
+            }
+            _ => speed(self.dl_now + self.ul_now, spent),
+        }
+    }
+}
+
+/// 统计经过的字节数并更新下载进度
+pub struct ProgressReader<'a, R: Read> {
+    inner: R,
+    progress: &'a mut Progress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, progress: &'a mut Progress) -> Self {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.progress.download(n);
+        Ok(n)
+    }
+}
+
+fn speed(bytes: u64, secs: f64) -> u64 {
+    if secs > 0.0 {
+        (bytes as f64 / secs) as u64
+    } else {
+        0
+    }
+}
+
+fn percent(now: u64, total: Option<u64>) -> u64 {
+    match total {
+        Some(0) => 100,
+        Some(total) => (now.min(total) * 100) / total,
+        None => 0,
+    }
+}
+
+/// 与 curl 一致, 用 5 个字符显示字节数
+fn format_size(bytes: u64) -> String {
+    const K: u64 = 1024;
+    const M: u64 = 1024 * K;
+    const G: u64 = 1024 * M;
+    if bytes < 100_000 {
+        format!("{:5}", bytes)
+    } else if bytes < 10_000 * K {
+        format!("{:4}k", bytes / K)
+    } else if bytes < 100 * M {
+        format!("{:2}.{}M", bytes / M, (bytes % M) * 10 / M)
+    } else if bytes < 10_000 * M {
+        format!("{:4}M", bytes / M)
+    } else if bytes < 100 * G {
+        format!("{:2}.{}G", bytes / G, (bytes % G) * 10 / G)
+    } else {
+        format!("{:4}G", bytes / G)
+    }
+}
+
+/// H:MM:SS, 未知时为 --:--:--
+fn format_time(secs: Option<f64>) -> String {
+    match secs {
+        Some(secs) => {
+            let secs = secs as u64;
+            let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+            if h < 100 {
+                format!("{:2}:{:02}:{:02}", h, m, s)
+            } else {
+                format!("{:3}d {:02}h", h / 24, h % 24)
+            }
+        }
+        None => "--:--:--".to_string(),
+    }
+}
+
+/// 终端宽度, 取 COLUMNS 环境变量, 默认 80
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|c| c.parse().ok())
+        .unwrap_or(80)
+}
+
+#[test]
+fn test_progress_format() {
+    assert_eq!(format_size(99_999), "99999");
+    assert_eq!(format_size(100_000), "  97k");
+    assert_eq!(format_size(15 * 1024 * 1024 + 512 * 1024), "15.5M");
+    assert_eq!(format_size(300 * 1024 * 1024), " 300M");
+    assert_eq!(format_time(Some(3725.0)), " 1:02:05");
+    assert_eq!(format_time(None), "--:--:--");
+
+    let mut progress = Progress::new(ProgressStyle::Bar);
+    progress.start_transfer(Some(100));
+    progress.ul_now = 100;
+    progress.set_download_total(Some(300));
+    progress.dl_now = 100;
+    let now = progress.start + Duration::from_secs(2);
+
+    let bar = progress.render_bar(now, 48);
+    assert_eq!(bar, format!("{:<40}  50.0%", "#".repeat(20)));
+
+    // 下载 100/300, 上传 100/100, 平均下载速度 50 B/s, 还需 4 秒
+    let meter = progress.render_meter(now);
+    assert_eq!(
+        meter,
+        " 50   400   33   100  100   100     50     50  0:00:06  0:00:02  0:00:04   100"
+    );
+
+    progress.set_download_total(None);
+    assert!(progress
+        .render_meter(now)
+        .starts_with("  0     0    0   100"));
+    assert!(progress.render_bar(now, 48).contains("#=#=#"));
+}
@@ -1,103 +1,786 @@
 use std::collections::HashMap;
+use std::io::{self, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-#[derive(Clone, Debug, PartialEq, Eq)]
-pub enum ResponseBody {
-    Text(String),
-    Binary(Vec<u8>),
-}
+use crate::encoding;
+use crate::multipart::BytePart;
 
+// 响应头, chunked 尾部字段和 multipart 分段头部的最大长度, 超过时认为服务器出错
+pub const MAX_HEAD_SIZE: usize = 100 * 1024;
+// chunk-size 行 (包括扩展参数) 的最大长度
+const MAX_CHUNK_LINE: usize = 4 * 1024;
+// 一个响应最多记录的 chunk 扩展参数个数, 之后的扩展参数被忽略
+const MAX_CHUNK_EXTENSIONS: usize = 64;
 
-/// HTTP响应结构体，支持文本和二进制类型响应数据
+/// HTTP响应头部, 响应体通过 BodyReader 流式读取
 #[derive(Clone, Debug)]
 pub struct Response {
-    pub raw: Vec<u8>,                     // 原始响应数据
-    pub headers: HashMap<String, String>, // 头部字段
-    pub body: ResponseBody,            // 响应体
+    pub head: Vec<u8>,                                   // 原始状态行和头部
+    pub status: u16,                                     // 状态码
+    pub headers: HashMap<String, String>,                // 头部字段
+    pub trailers: HashMap<String, String>,               // chunked 编码的尾部字段
+    pub chunk_extensions: Vec<(String, Option<String>)>, // 各 chunk 的扩展参数, 按顺序排列
+    pub parts: Vec<BytePart>,                            // multipart/byteranges 的分段
 }
 
 impl Response {
-    pub fn parse(raw: Vec<u8>) -> Self {
-        let delimiter = b"\r\n\r\n";
-        if let Some(pos) = raw.windows(delimiter.len()).position(|window| window == delimiter) {
-            let header_bytes = &raw[..pos + delimiter.len()];
-            let body_bytes = raw[pos + delimiter.len()..].to_vec();
-
-            let header_str = String::from_utf8_lossy(header_bytes);
-            let mut headers = HashMap::new();
-            for line in header_str.lines().skip(1) {
-                if let Some((k, v)) = line.split_once(":") {
-                    headers.insert(k.trim().to_string(), v.trim().to_string());
+    /*
+    以下函数在dragonos中不可用
+    read_to_end
+    read_to_string
+    read_to_vec
+     */
+    /// 读取状态行和头部, 跳过 1xx 中间响应.
+    /// 返回响应头以及读取头部时一并读到的响应体数据
+    pub fn read_head<R: Read>(stream: &mut R) -> io::Result<(Self, Vec<u8>)> {
+        let mut buffer = [0u8; 1024];
+        let mut result = Vec::new();
+        loop {
+            if let Some(end) = find_head_end(&result) {
+                let (status, headers) = parse_head(&result[..end]);
+                if (100..200).contains(&status) && status != 101 {
+                    result.drain(..end);
+                    continue;
                 }
+                let rest = result.split_off(end);
+                let resp = Response {
+                    head: result,
+                    status,
+                    headers,
+                    trailers: HashMap::new(),
+                    chunk_extensions: Vec::new(),
+                    parts: Vec::new(),
+                };
+                return Ok((resp, rest));
             }
+            if result.len() > MAX_HEAD_SIZE {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "response headers too large",
+                ));
+            }
+            let n = read_some(stream, &mut buffer)?;
+            if n == 0 {
+                let msg = if result.is_empty() {
+                    "empty reply from server"
+                } else {
+                    "connection closed before the end of response headers"
+                };
+                return Err(io::Error::new(io::ErrorKind::UnexpectedEof, msg));
+            }
+            result.extend_from_slice(&buffer[..n]);
+        }
+    }
 
-            let body = Self::detect_body(&headers, body_bytes);
+    pub fn content_range(&self) -> Option<ContentRange> {
+        get_header(&self.headers, "Content-Range").and_then(ContentRange::parse)
+    }
 
-            Response {
-                raw,
-                headers,
-                body,
+    /// Retry-After 头, 秒数或 HTTP-date, 返回距 now 还需等待的时间
+    pub fn retry_after(&self, now: SystemTime) -> Option<Duration> {
+        let value = get_header(&self.headers, "Retry-After")?.trim();
+        if let Ok(secs) = value.parse::<u64>() {
+            return Some(Duration::from_secs(secs));
+        }
+        let date = parse_http_date(value)?;
+        Some(date.duration_since(now).unwrap_or_default())
+    }
+
+    /// 按 Content-Encoding 包装响应体, 逐层解码
+    pub fn content_decoder<'a>(&self, body: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        match get_header(&self.headers, "Content-Encoding") {
+            Some(ce) => encoding::decoder(&encoding::parse_encodings(ce)?, body),
+            None => Ok(body),
+        }
+    }
+}
+
+/// 根据 Content-Type 和响应体开头判断是否为文本, 二进制内容不直接输出到终端
+pub fn is_text(content_type: Option<&str>, first_chunk: &[u8]) -> bool {
+    if let Some(content_type) = content_type {
+        let ct = content_type.to_ascii_lowercase();
+        if !(ct.starts_with("text/")
+            || ct.contains("json")
+            || ct.contains("xml")
+            || ct.contains("html"))
+        {
+            return false;
+        }
+    }
+    // 末尾可能是被截断的多字节字符
+    !first_chunk.contains(&0)
+        && match std::str::from_utf8(first_chunk) {
+            Ok(_) => true,
+            Err(e) => e.error_len().is_none(),
+        }
+}
+
+/// Content-Range 头, 例如 `bytes 0-499/1234` 或 `bytes */1234`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ContentRange {
+    pub range: Option<(u64, u64)>, // 闭区间 [start, end], 416 响应中为 None
+    pub total: Option<u64>,        // 完整长度, 未知时为 None
+}
+
+impl ContentRange {
+    pub fn parse(value: &str) -> Option<Self> {
+        let spec = value.trim().strip_prefix("bytes")?.trim_start();
+        let (range, total) = spec.split_once('/')?;
+        let total = match total.trim() {
+            "*" => None,
+            t => Some(t.parse().ok()?),
+        };
+        let range = match range.trim() {
+            "*" => None,
+            r => {
+                let (start, end) = r.split_once('-')?;
+                let (start, end) = (start.trim().parse().ok()?, end.trim().parse().ok()?);
+                if start > end {
+                    return None;
+                }
+                Some((start, end))
             }
-        } else {
-            let body = Self::detect_body(&HashMap::new(), raw.clone());
-            Response {
-                raw: raw.clone(),
-                headers: HashMap::new(),
-                body,
+        };
+        Some(ContentRange { range, total })
+    }
+}
+
+/// IMF-fixdate, 例如 `Sun, 06 Nov 1994 08:49:37 GMT`
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    let (_, rest) = value.split_once(", ")?;
+    let fields: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = fields[..] else {
+        return None;
+    };
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let month = MONTHS.iter().position(|m| *m == month)? as i64 + 1;
+    let (day, year): (i64, i64) = (day.parse().ok()?, year.parse().ok()?);
+    let hms: Vec<u64> = time
+        .split(':')
+        .map(|t| t.parse().ok())
+        .collect::<Option<_>>()?;
+    let [h, m, s] = hms[..] else {
+        return None;
+    };
+    if !(1..=31).contains(&day) || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+
+    // 公历日期到 1970-01-01 的天数
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = u64::try_from(era * 146097 + doe - 719468).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(days * 86400 + h * 3600 + m * 60 + s))
+}
+
+/// 读取一次数据, 返回 0 表示连接已关闭. 被信号中断时重新读取, 其他错误直接返回
+fn read_some<R: std::io::Read>(stream: &mut R, buffer: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match stream.read(buffer) {
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+/// 头部结束位置 (包含 \r\n\r\n)
+fn find_head_end(raw: &[u8]) -> Option<usize> {
+    let delimiter = b"\r\n\r\n";
+    raw.windows(delimiter.len())
+        .position(|window| window == delimiter)
+        .map(|pos| pos + delimiter.len())
+}
+
+/// 解析状态行和头部字段
+fn parse_head(head: &[u8]) -> (u16, HashMap<String, String>) {
+    let head_str = String::from_utf8_lossy(head);
+    let mut lines = head_str.lines();
+    let status = lines
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .and_then(|code| code.parse().ok())
+        .unwrap_or(0);
+    let mut headers = HashMap::new();
+    for line in lines {
+        if let Some((k, v)) = line.split_once(":") {
+            headers.insert(k.trim().to_string(), v.trim().to_string());
+        }
+    }
+    (status, headers)
+}
+
+/// HTTP/1.1 默认保持连接, HTTP/1.0 需要显式的 keep-alive
+fn keep_alive(head: &[u8], headers: &HashMap<String, String>) -> bool {
+    let connection = get_header(headers, "Connection")
+        .unwrap_or("")
+        .to_ascii_lowercase();
+    if connection.contains("close") {
+        return false;
+    }
+    head.starts_with(b"HTTP/1.1") || connection.contains("keep-alive")
+}
+
+fn content_length(headers: &HashMap<String, String>) -> io::Result<Option<usize>> {
+    match get_header(headers, "Content-Length") {
+        Some(len) => len.trim().parse().map(Some).map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid Content-Length: {:?}", len),
+            )
+        }),
+        None => Ok(None),
+    }
+}
+
+/// 按名称查找头部字段, 不区分大小写
+pub fn get_header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+/// Transfer-Encoding 的最后一个编码是否为 chunked
+fn is_chunked(headers: &HashMap<String, String>) -> bool {
+    get_header(headers, "Transfer-Encoding")
+        .and_then(|te| te.rsplit(',').next())
+        .map(|last| last.trim().eq_ignore_ascii_case("chunked"))
+        .unwrap_or(false)
+}
+
+enum ChunkState {
+    Size,        // 读取 chunk-size [; ext] CRLF
+    Data(usize), // 剩余的 chunk 数据长度
+    DataEnd,     // chunk 数据后的 CRLF
+    Trailer,     // 尾部字段, 以空行结束
+    Done,
+}
+
+/// chunked 传输编码的增量解码器, 可以分多次喂入数据
+pub struct ChunkedDecoder {
+    state: ChunkState,
+    line: Vec<u8>,
+    trailer_len: usize,                            // 已读取的尾部字段长度
+    pub extensions: Vec<(String, Option<String>)>, // 已解码 chunk 的扩展参数
+    pub trailers: HashMap<String, String>,
+}
+
+impl ChunkedDecoder {
+    pub fn new() -> Self {
+        ChunkedDecoder {
+            state: ChunkState::Size,
+            line: Vec::new(),
+            trailer_len: 0,
+            extensions: Vec::new(),
+            trailers: HashMap::new(),
+        }
+    }
+
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, ChunkState::Done)
+    }
+
+    /// 解码 input 并把数据追加到 out, 返回消费的字节数.
+    /// 最后一个 chunk 之后的字节不会被消费.
+    pub fn decode(&mut self, input: &[u8], out: &mut Vec<u8>) -> io::Result<usize> {
+        let mut pos = 0;
+        while pos < input.len() {
+            match self.state {
+                ChunkState::Done => break,
+                ChunkState::Data(remaining) => {
+                    let n = remaining.min(input.len() - pos);
+                    out.extend_from_slice(&input[pos..pos + n]);
+                    pos += n;
+                    self.state = if n == remaining {
+                        ChunkState::DataEnd
+                    } else {
+                        ChunkState::Data(remaining - n)
+                    };
+                }
+                ChunkState::Size | ChunkState::DataEnd | ChunkState::Trailer => {
+                    let b = input[pos];
+                    pos += 1;
+                    if b != b'\n' {
+                        if self.line.len() >= self.line_limit() {
+                            return Err(io::Error::new(
+                                io::ErrorKind::InvalidData,
+                                "chunk size line or trailers too long",
+                            ));
+                        }
+                        self.line.push(b);
+                        continue;
+                    }
+                    if self.line.last() == Some(&b'\r') {
+                        self.line.pop();
+                    }
+                    let line = String::from_utf8_lossy(&self.line).into_owned();
+                    self.line.clear();
+                    self.on_line(&line)?;
+                }
             }
         }
+        Ok(pos)
+    }
+
+    /// 当前行允许的最大长度, 尾部字段合计不超过 MAX_HEAD_SIZE
+    fn line_limit(&self) -> usize {
+        match self.state {
+            ChunkState::Trailer => MAX_HEAD_SIZE.saturating_sub(self.trailer_len),
+            _ => MAX_CHUNK_LINE,
+        }
     }
 
-    fn detect_body(headers: &HashMap<String, String>, body_bytes: Vec<u8>) -> ResponseBody {
-        if let Some(content_type) = headers.get("Content-Type") {
-            let ct = content_type.to_ascii_lowercase();
-            if ct.starts_with("text/")
-                || ct.contains("json")
-                || ct.contains("xml")
-                || ct.contains("html")
-            {
-                // 文本类型
-                if let Ok(s) = String::from_utf8(body_bytes) {
-                    ResponseBody::Text(s)
+    fn on_line(&mut self, line: &str) -> io::Result<()> {
+        match self.state {
+            ChunkState::Size => {
+                let mut parts = line.split(';');
+                let size_str = parts.next().unwrap_or("").trim();
+                let size = usize::from_str_radix(size_str, 16).map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid chunk size: {:?}", size_str),
+                    )
+                })?;
+                for ext in parts.take(MAX_CHUNK_EXTENSIONS - self.extensions.len()) {
+                    let (name, value) = match ext.split_once('=') {
+                        Some((n, v)) => (n, Some(v.trim().trim_matches('"').to_string())),
+                        None => (ext, None),
+                    };
+                    self.extensions.push((name.trim().to_string(), value));
+                }
+                self.state = if size == 0 {
+                    ChunkState::Trailer
                 } else {
-                    ResponseBody::Binary(Vec::new())
+                    ChunkState::Data(size)
+                };
+            }
+            ChunkState::DataEnd => {
+                if !line.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "missing CRLF after chunk data",
+                    ));
                 }
-            } else {
-                ResponseBody::Binary(body_bytes)
+                self.state = ChunkState::Size;
             }
-        } else {
-            // 没有 Content-Type，尝试按 utf8 解码
-            match String::from_utf8(body_bytes) {
-                Ok(s) => ResponseBody::Text(s),
-                Err(e) => ResponseBody::Binary(e.into_bytes()),
+            ChunkState::Trailer => {
+                self.trailer_len += line.len() + 2;
+                if line.is_empty() {
+                    self.state = ChunkState::Done;
+                } else if let Some((k, v)) = line.split_once(':') {
+                    self.trailers
+                        .insert(k.trim().to_string(), v.trim().to_string());
+                }
             }
+            ChunkState::Data(_) | ChunkState::Done => unreachable!(),
         }
+        Ok(())
     }
+}
 
-    /*
-    以下函数在dragonos中不可用
-    read_to_end
-    read_to_string
-    read_to_vec
-     */
-    /// 读取流中所有内容，返回 Vec<u8>
-    pub fn read<R: std::io::Read>(stream: &mut R) -> std::io::Result<Vec<u8>> {
-        let mut buffer = [0u8; 1024];
-        let mut result = Vec::new();
+enum Framing {
+    Empty,         // 没有响应体
+    Length(usize), // 剩余的 Content-Length 字节数
+    Chunked(ChunkedDecoder),
+    UntilClose, // 读到连接关闭
+}
+
+/// 按 Content-Length / chunked / 连接关闭划分响应体的 reader.
+/// 每次只从连接读取一块数据, 内存占用与响应体大小无关
+pub struct BodyReader<'a, R: Read> {
+    stream: &'a mut R,
+    pending: Vec<u8>, // 已从连接读取但还未处理的数据
+    decoded: Vec<u8>, // chunked 解码后还未返回的数据
+    decoded_pos: usize,
+    framing: Framing,
+    keep_alive: bool,
+    done: bool,
+    received: u64, // 已返回的响应体字节数
+}
+
+impl<'a, R: Read> BodyReader<'a, R> {
+    /// pending 为 read_head 多读到的数据. expects_body 为 false 时 (HEAD) 没有响应体
+    pub fn new(
+        stream: &'a mut R,
+        resp: &Response,
+        pending: Vec<u8>,
+        expects_body: bool,
+    ) -> io::Result<Self> {
+        let status = resp.status;
+        let framing = if !expects_body || status == 204 || status == 304 || status < 200 {
+            Framing::Empty
+        } else if is_chunked(&resp.headers) {
+            Framing::Chunked(ChunkedDecoder::new())
+        } else if let Some(len) = content_length(&resp.headers)? {
+            Framing::Length(len)
+        } else {
+            Framing::UntilClose
+        };
+        let keep_alive =
+            keep_alive(&resp.head, &resp.headers) && !matches!(framing, Framing::UntilClose);
+        Ok(BodyReader {
+            stream,
+            pending,
+            decoded: Vec::new(),
+            decoded_pos: 0,
+            framing,
+            keep_alive,
+            done: false,
+            received: 0,
+        })
+    }
+
+    pub fn has_body(&self) -> bool {
+        !matches!(self.framing, Framing::Empty | Framing::Length(0))
+    }
+
+    /// 还未读取的响应体长度, chunked 或读到连接关闭时未知
+    pub fn remaining_len(&self) -> Option<u64> {
+        match self.framing {
+            Framing::Empty => Some(0),
+            Framing::Length(n) => Some(n as u64),
+            Framing::Chunked(_) | Framing::UntilClose => None,
+        }
+    }
+
+    /// 已读取的响应体字节数 (chunked 解码后)
+    pub fn received(&self) -> u64 {
+        self.received
+    }
+
+    /// 响应体已读完且没有多余数据时, 连接可以复用
+    pub fn reusable(&self) -> bool {
+        self.done && self.keep_alive && self.pending.is_empty()
+    }
+
+    /// 把 chunked 编码的尾部字段和扩展参数移到 resp 中
+    pub fn take_trailers(&mut self, resp: &mut Response) {
+        if let Framing::Chunked(decoder) = &mut self.framing {
+            resp.trailers = std::mem::take(&mut decoder.trailers);
+            resp.chunk_extensions = std::mem::take(&mut decoder.extensions);
+        }
+    }
+}
+
+impl<R: Read> Read for BodyReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.read_body(buf)?;
+        self.received += n as u64;
+        Ok(n)
+    }
+}
+
+impl<R: Read> BodyReader<'_, R> {
+    fn read_body(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
         loop {
-            match stream.read(&mut buffer) {
-                Ok(0) => break,
-                Ok(n) => {
-                    result.extend_from_slice(&buffer[..n]);
+            match &mut self.framing {
+                Framing::Empty | Framing::Length(0) => {
+                    self.done = true;
+                    return Ok(0);
+                }
+                Framing::Length(remaining) => {
+                    if self.pending.is_empty() && fill(self.stream, &mut self.pending)? == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!("transfer closed with {} bytes remaining", remaining),
+                        ));
+                    }
+                    let n = (*remaining).min(self.pending.len()).min(buf.len());
+                    buf[..n].copy_from_slice(&self.pending[..n]);
+                    self.pending.drain(..n);
+                    *remaining -= n;
+                    return Ok(n);
+                }
+                Framing::Chunked(decoder) => {
+                    if self.decoded_pos < self.decoded.len() {
+                        let n = (self.decoded.len() - self.decoded_pos).min(buf.len());
+                        buf[..n]
+                            .copy_from_slice(&self.decoded[self.decoded_pos..self.decoded_pos + n]);
+                        self.decoded_pos += n;
+                        return Ok(n);
+                    }
+                    if decoder.is_done() {
+                        self.done = true;
+                        return Ok(0);
+                    }
+                    if self.pending.is_empty() && fill(self.stream, &mut self.pending)? == 0 {
+                        return Err(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            "chunked body ended before the last chunk",
+                        ));
+                    }
+                    self.decoded.clear();
+                    self.decoded_pos = 0;
+                    let used = decoder.decode(&self.pending, &mut self.decoded)?;
+                    self.pending.drain(..used);
                 }
-                Err(e) => {
-                    println!("与linux不一致: {:?}", e);
-                    break;
+                Framing::UntilClose => {
+                    if self.pending.is_empty() && fill(self.stream, &mut self.pending)? == 0 {
+                        self.done = true;
+                        return Ok(0);
+                    }
+                    let n = self.pending.len().min(buf.len());
+                    buf[..n].copy_from_slice(&self.pending[..n]);
+                    self.pending.drain(..n);
+                    return Ok(n);
                 }
             }
         }
-        Ok(result)
     }
+}
+
+/// 从连接再读取一块数据到 pending
+fn fill<R: Read>(stream: &mut R, pending: &mut Vec<u8>) -> io::Result<usize> {
+    let mut buffer = [0u8; 8192];
+    let n = read_some(stream, &mut buffer)?;
+    pending.extend_from_slice(&buffer[..n]);
+    Ok(n)
+}
+
+#[cfg(test)]
+fn read_response(data: &[u8], expects_body: bool) -> (Response, Vec<u8>, bool) {
+    let mut stream = data;
+    let (mut resp, pending) = Response::read_head(&mut stream).unwrap();
+    let mut reader = BodyReader::new(&mut stream, &resp, pending, expects_body).unwrap();
+    let mut body = Vec::new();
+    let mut buffer = [0u8; 7];
+    loop {
+        let n = reader.read(&mut buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        body.extend_from_slice(&buffer[..n]);
+    }
+    reader.take_trailers(&mut resp);
+    let reusable = reader.reusable();
+    (resp, body, reusable)
+}
+
+#[test]
+fn test_read_stops_at_content_length() {
+    // 连接不关闭, 读取应在 Content-Length 处结束
+    let data = b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+    let (resp, body, reusable) = read_response(data, true);
+    assert_eq!(resp.status, 200);
+    assert_eq!(body, b"hello");
+    assert!(reusable);
+
+    // 读到了属于下一个响应的数据
+    let data = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhelloHTTP/1.1 200 OK";
+    let (_, body, reusable) = read_response(data, true);
+    assert_eq!(body, b"hello");
+    assert!(!reusable);
+
+    let data = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\n";
+    let (_, body, reusable) = read_response(data, false);
+    assert!(body.is_empty() && reusable);
+
+    let data = b"HTTP/1.0 200 OK\r\nContent-Length: 2\r\n\r\nhi";
+    assert!(!read_response(data, true).2);
+    assert!(Response::read_head(&mut &b""[..]).is_err());
+    // 没有结束的响应头不会无限读取
+    let head = format!(
+        "HTTP/1.1 200 OK\r\n{}",
+        "X-Pad: y\r\n".repeat(MAX_HEAD_SIZE / 10)
+    );
+    assert!(Response::read_head(&mut head.as_bytes()).is_err());
+}
+
+#[test]
+fn test_read_truncated_body() {
+    let data = b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nhello";
+    let mut stream = &data[..];
+    let (resp, pending) = Response::read_head(&mut stream).unwrap();
+    let mut reader = BodyReader::new(&mut stream, &resp, pending, true).unwrap();
+    let mut buffer = [0u8; 16];
+    assert_eq!(reader.read(&mut buffer).unwrap(), 5);
+    assert!(reader.read(&mut buffer).is_err());
+}
+
+#[test]
+fn test_read_interrupted() {
+    // 每次读取前先返回一次 EINTR, 数据读完后连接被重置
+    struct Flaky<'a> {
+        data: &'a [u8],
+        interrupt: bool,
+    }
+    impl Read for Flaky<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            if self.data.is_empty() {
+                return Err(io::ErrorKind::ConnectionReset.into());
+            }
+            let n = self.data.len().min(buf.len()).min(7);
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            Ok(n)
+        }
+    }
+    let mut stream = Flaky {
+        data: b"HTTP/1.1 200 OK\r\n\r\nread until close",
+        interrupt: false,
+    };
+    let (resp, pending) = Response::read_head(&mut stream).unwrap();
+    let mut reader = BodyReader::new(&mut stream, &resp, pending, true).unwrap();
+    let mut body = Vec::new();
+    let err = reader.read_to_end(&mut body).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+    assert_eq!(body, b"read until close");
+}
+
+#[test]
+fn test_read_stops_at_last_chunk() {
+    let data = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n";
+    let (_, body, reusable) = read_response(data, true);
+    assert_eq!(body, b"abc");
+    assert!(reusable);
+}
+
+#[test]
+fn test_content_range() {
+    assert_eq!(
+        ContentRange::parse("bytes 100-199/1000"),
+        Some(ContentRange {
+            range: Some((100, 199)),
+            total: Some(1000)
+        })
+    );
+    assert_eq!(
+        ContentRange::parse("bytes 0-9/*"),
+        Some(ContentRange {
+            range: Some((0, 9)),
+            total: None
+        })
+    );
+    assert_eq!(
+        ContentRange::parse("bytes */1000"),
+        Some(ContentRange {
+            range: None,
+            total: Some(1000)
+        })
+    );
+    assert_eq!(ContentRange::parse("bytes 9-0/10"), None);
+    assert_eq!(ContentRange::parse("items 0-1/2"), None);
+}
+
+#[test]
+fn test_retry_after() {
+    let (mut resp, _, _) = read_response(b"HTTP/1.1 503 Service Unavailable\r\n\r\n", false);
+    let now = UNIX_EPOCH + Duration::from_secs(784111777); // Sun, 06 Nov 1994 08:49:37 GMT
+    assert_eq!(resp.retry_after(now), None);
+
+    resp.headers
+        .insert("Retry-After".to_string(), "120".to_string());
+    assert_eq!(resp.retry_after(now), Some(Duration::from_secs(120)));
+
+    resp.headers.insert(
+        "Retry-After".to_string(),
+        "Sun, 06 Nov 1994 08:50:07 GMT".to_string(),
+    );
+    assert_eq!(resp.retry_after(now), Some(Duration::from_secs(30)));
+    // 已经过去的时间不需要等待
+    assert_eq!(
+        resp.retry_after(now + Duration::from_secs(60)),
+        Some(Duration::ZERO)
+    );
+
+    resp.headers
+        .insert("Retry-After".to_string(), "soon".to_string());
+    assert_eq!(resp.retry_after(now), None);
+}
+
+#[test]
+fn test_content_decoder() {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut gzip = GzEncoder::new(Vec::new(), Compression::default());
+    gzip.write_all(b"{\"ok\":true}").unwrap();
+    let compressed = gzip.finish().unwrap();
+    let mut data = format!(
+        "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Encoding: gzip\r\nContent-Length: {}\r\n\r\n",
+        compressed.len()
+    )
+    .into_bytes();
+    data.extend_from_slice(&compressed);
+
+    let (resp, body, _) = read_response(&data, true);
+    // 不解码时保留原始压缩数据, 并且不按文本输出
+    assert_eq!(body, compressed);
+    let content_type = get_header(&resp.headers, "Content-Type");
+    assert!(!is_text(content_type, &body));
+
+    let mut decoded = Vec::new();
+    let mut reader = resp.content_decoder(Box::new(&body[..])).unwrap();
+    let mut buffer = [0u8; 64];
+    loop {
+        let n = reader.read(&mut buffer).unwrap();
+        if n == 0 {
+            break;
+        }
+        decoded.extend_from_slice(&buffer[..n]);
+    }
+    assert_eq!(decoded, b"{\"ok\":true}");
+    assert!(is_text(content_type, &decoded));
+}
+
+#[test]
+fn test_parse_chunked() {
+    let raw = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nTransfer-Encoding: chunked\r\n\r\n\
+4;name=value\r\nWiki\r\n6;flag\r\npedia \r\nE\r\nin \r\n\r\nchunks.\r\n0\r\nExpires: never\r\n\r\n";
+    let (resp, body, _) = read_response(raw, true);
+    assert_eq!(body, b"Wikipedia in \r\n\r\nchunks.");
+    assert_eq!(resp.trailers.get("Expires").unwrap(), "never");
+    assert_eq!(
+        resp.chunk_extensions,
+        vec![
+            ("name".to_string(), Some("value".to_string())),
+            ("flag".to_string(), None)
+        ]
+    );
+}
+
+#[test]
+fn test_chunked_decoder_split_input() {
+    let body = b"5\r\nhello\r\n0\r\n\r\nextra";
+    let mut decoder = ChunkedDecoder::new();
+    let mut out = Vec::new();
+    let mut consumed = 0;
+    for piece in body.chunks(3) {
+        consumed += decoder.decode(piece, &mut out).unwrap();
+    }
+    assert!(decoder.is_done());
+    assert_eq!(out, b"hello");
+    assert_eq!(consumed, body.len() - "extra".len());
+
+    let mut decoder = ChunkedDecoder::new();
+    assert!(decoder.decode(b"zz\r\n", &mut Vec::new()).is_err());
 
+    // 过长的 chunk-size 行和尾部字段
+    let mut decoder = ChunkedDecoder::new();
+    let line = format!("5;{}", "x".repeat(MAX_CHUNK_LINE));
+    assert!(decoder.decode(line.as_bytes(), &mut Vec::new()).is_err());
+    let mut decoder = ChunkedDecoder::new();
+    decoder.decode(b"0\r\n", &mut Vec::new()).unwrap();
+    let trailer = "X-Pad: y\r\n".repeat(MAX_HEAD_SIZE / 10 + 1);
+    assert!(decoder.decode(trailer.as_bytes(), &mut Vec::new()).is_err());
 
-    
+    // 扩展参数的个数有上限
+    let mut decoder = ChunkedDecoder::new();
+    let body = "1;a;b\r\nx\r\n".repeat(MAX_CHUNK_EXTENSIONS);
+    decoder.decode(body.as_bytes(), &mut Vec::new()).unwrap();
+    assert_eq!(decoder.extensions.len(), MAX_CHUNK_EXTENSIONS);
 }
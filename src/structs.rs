@@ -1,31 +1,82 @@
-use std::{collections::HashMap, f32::MIN_POSITIVE};
+use std::collections::HashMap;
+use std::fmt;
 
-#[derive(Copy, Clone)]
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Method {
     GET,
     POST,
-    UNKNOWN,
+    PUT,
+    DELETE,
+    PATCH,
+    HEAD,
+    OPTIONS,
+    CONNECT,
+    TRACE,
+    // -X 传入的其他方法, 原样发送
+    CUSTOM(String),
 }
 
 impl Method {
-    pub fn to_string(&self) -> String{
-        match *self {
-            Method::GET => String::from("GET"),
-            Method::POST => String::from("POST"),
-            Method::UNKNOWN => String::from("UNKNOWN"),
-        }
-    }
-
     pub fn from(method_str: &str) -> Self {
         match method_str {
             "GET" => Self::GET,
             "POST" => Self::POST,
-            _ => Self::UNKNOWN,
+            "PUT" => Self::PUT,
+            "DELETE" => Self::DELETE,
+            "PATCH" => Self::PATCH,
+            "HEAD" => Self::HEAD,
+            "OPTIONS" => Self::OPTIONS,
+            "CONNECT" => Self::CONNECT,
+            "TRACE" => Self::TRACE,
+            other => Self::CUSTOM(other.to_string()),
         }
     }
+
+    /// 方法名是否为合法的 token (RFC 9110 5.6.2)
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Method::CUSTOM(m) => {
+                !m.is_empty()
+                    && m.bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b))
+            }
+            _ => true,
+        }
+    }
+
+    /// 是否发送 -d/-F 设置的请求体
+    pub fn sends_body(&self) -> bool {
+        !matches!(self, Method::GET | Method::HEAD)
+    }
+
+    /// 响应是否带有响应体, HEAD 的响应只有头部
+    pub fn expects_response_body(&self) -> bool {
+        !matches!(self, Method::HEAD)
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Method::GET => "GET",
+            Method::POST => "POST",
+            Method::PUT => "PUT",
+            Method::DELETE => "DELETE",
+            Method::PATCH => "PATCH",
+            Method::HEAD => "HEAD",
+            Method::OPTIONS => "OPTIONS",
+            Method::CONNECT => "CONNECT",
+            Method::TRACE => "TRACE",
+            Method::CUSTOM(m) => m,
+        };
+        f.write_str(s)
+    }
 }
 
 // content type
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone)]
 pub enum Contype {
     FORM,     // application/x-www-form-urlencoded
     FORMDATA(String), // multipart/form-data
@@ -35,25 +86,27 @@ pub enum Contype {
     // STREAM,   //application/octet-stream
 }
 
-impl Contype {
-    pub fn to_string(&self) -> String {
+impl fmt::Display for Contype {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Contype::FORM => String::from("application/x-www-form-urlencoded"),
-            Contype::FORMDATA(boundary) => format!("multipart/form-data; boundary={}", boundary),
-            Contype::JSON => String::from("application/json"),
-            // Contype::XML => String::from("text/xml"),
-            // Contype::TEXT => String::from("text/plain"),
-            // Contype::STREAM => String::from("application/octet-stream"),
+            Contype::FORM => write!(f, "application/x-www-form-urlencoded"),
+            Contype::FORMDATA(boundary) => write!(f, "multipart/form-data; boundary={}", boundary),
+            Contype::JSON => write!(f, "application/json"),
+            // Contype::XML => write!(f, "text/xml"),
+            // Contype::TEXT => write!(f, "text/plain"),
+            // Contype::STREAM => write!(f, "application/octet-stream"),
         }
     }
 }
 
+#[derive(Clone)]
 struct RequestLine {
     pub method: Method,
     pub path: String,
     pub version: String,
 }
 
+#[derive(Clone)]
 pub struct Header {
     request_line: Option<RequestLine>,
     header: HashMap<String, String>,
@@ -82,23 +135,18 @@ impl Header {
         self
     }
 
-   
-
     pub fn set(&mut self, key: &str, value: &str) -> &mut Self {
         self.header.insert(key.to_string(), value.to_string());
         self
     }
 
-    pub fn to_string(&self) -> String {
-        let mut lines = Vec::new();
-        if let Some(ref req) = self.request_line {
-            let method_str = req.method.to_string();
-            lines.push(format!("{} {} {}", method_str, req.path, req.version));
-        }
-        lines.extend(self.header.iter().map(|(k, v)| format!("{}: {}", k, v)));
-        lines.join("\r\n")
+    /// 删除头部字段, 不区分大小写
+    pub fn remove(&mut self, key: &str) -> &mut Self {
+        self.header.retain(|k, _| !k.eq_ignore_ascii_case(key));
+        self
     }
 
+    #[cfg(test)]
     pub fn from(s: &str) -> Self {
         let mut lines = s.lines();
         let mut request_line = None;
@@ -107,11 +155,7 @@ impl Header {
         if let Some(first) = lines.next() {
             let parts: Vec<&str> = first.split_whitespace().collect();
             if parts.len() == 3 {
-                let method = match parts[0] {
-                    "GET" => Method::GET,
-                    "POST" => Method::POST,
-                    _ => Method::GET, // 默认
-                };
+                let method = Method::from(parts[0]);
                 request_line = Some(RequestLine {
                     method,
                     path: parts[1].to_string(),
@@ -135,7 +179,16 @@ impl Header {
     }
 }
 
-
+impl fmt::Display for Header {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut lines = Vec::new();
+        if let Some(ref req) = self.request_line {
+            lines.push(format!("{} {} {}", req.method, req.path, req.version));
+        }
+        lines.extend(self.header.iter().map(|(k, v)| format!("{}: {}", k, v)));
+        write!(f, "{}", lines.join("\r\n"))
+    }
+}
 
 #[test]
 fn test_header_from() {
@@ -157,3 +210,19 @@ fn test_header_from() {
     assert_eq!(header.header.get("Accept").unwrap(), "*/*");
     assert_eq!(header.header.get("Content-Type").unwrap(), "text/plain");
 }
+
+#[test]
+fn test_method_from() {
+    assert_eq!(Method::from("PUT"), Method::PUT);
+    assert_eq!(Method::from("HEAD"), Method::HEAD);
+    assert_eq!(
+        Method::from("PROPFIND"),
+        Method::CUSTOM("PROPFIND".to_string())
+    );
+    assert_eq!(Method::from("PROPFIND").to_string(), "PROPFIND");
+    assert!(Method::from("PROPFIND").is_valid());
+    assert!(!Method::from("BAD METHOD").is_valid());
+    assert!(!Method::HEAD.expects_response_body());
+    assert!(Method::DELETE.sends_body());
+    assert!(!Method::GET.sends_body());
+}
@@ -0,0 +1,155 @@
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::pool::Connection;
+
+/// 一次传输各阶段的时间点, 都从传输开始计算, 与 curl 的 time_* 相同.
+/// 复用连接时 namelookup/connect/appconnect 为 0
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub namelookup: Duration,    // DNS 解析完成
+    pub connect: Duration,       // TCP 连接建立
+    pub appconnect: Duration,    // TLS 握手完成, 明文连接为 0
+    pub pretransfer: Duration,   // 开始发送请求
+    pub starttransfer: Duration, // 收到响应的第一个字节
+    pub redirect: Duration,      // 跟随重定向时, 最后一次请求开始之前的时间
+    pub total: Duration,
+}
+
+impl Timing {
+    /// DNS 解析耗时
+    pub fn dns(&self) -> Duration {
+        self.namelookup
+    }
+
+    /// TCP 连接耗时
+    pub fn tcp_connect(&self) -> Duration {
+        self.connect.saturating_sub(self.namelookup)
+    }
+
+    /// TLS 握手耗时
+    pub fn tls_handshake(&self) -> Duration {
+        self.appconnect.saturating_sub(self.connect)
+    }
+
+    /// 发出请求到收到第一个字节的时间
+    pub fn ttfb(&self) -> Duration {
+        self.starttransfer.saturating_sub(self.pretransfer)
+    }
+}
+
+/// 记录第一次读到数据的时间
+pub struct FirstByte<'a, R: Read> {
+    inner: &'a mut R,
+    pub at: Option<Instant>,
+}
+
+impl<'a, R: Read> FirstByte<'a, R> {
+    pub fn new(inner: &'a mut R) -> Self {
+        FirstByte { inner, at: None }
+    }
+}
+
+impl<R: Read> Read for FirstByte<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 && self.at.is_none() {
+            self.at = Some(Instant::now());
+        }
+        Ok(n)
+    }
+}
+
+/// -m/--max-time: 每次读写前把 socket 超时设为剩余时间, 超过期限返回 TimedOut
+pub struct DeadlineStream<'a> {
+    inner: &'a mut Connection,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineStream<'a> {
+    pub fn new(inner: &'a mut Connection, deadline: Instant) -> Self {
+        DeadlineStream { inner, deadline }
+    }
+
+    fn arm(&self) -> io::Result<()> {
+        let remaining = remaining(self.deadline)?;
+        self.inner.set_io_timeout(Some(remaining))
+    }
+}
+
+impl Read for DeadlineStream<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.arm()?;
+        self.inner.read(buf).map_err(timed_out)
+    }
+}
+
+impl Write for DeadlineStream<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.arm()?;
+        self.inner.write(buf).map_err(timed_out)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.arm()?;
+        self.inner.flush().map_err(timed_out)
+    }
+}
+
+/// 距离 deadline 的剩余时间, 已经超过时返回 TimedOut
+pub fn remaining(deadline: Instant) -> io::Result<Duration> {
+    let now = Instant::now();
+    if now >= deadline {
+        return Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "operation timed out",
+        ));
+    }
+    Ok(deadline - now)
+}
+
+/// socket 超时在不同平台上分别返回 WouldBlock 或 TimedOut, 统一为 TimedOut
+pub fn timed_out(e: io::Error) -> io::Error {
+    if e.kind() == io::ErrorKind::WouldBlock {
+        io::Error::new(io::ErrorKind::TimedOut, "operation timed out")
+    } else {
+        e
+    }
+}
+
+/// 是否为 --connect-timeout 或 -m 引起的超时
+pub fn is_timeout(e: &(dyn std::error::Error + 'static)) -> bool {
+    e.downcast_ref::<io::Error>()
+        .is_some_and(|e| e.kind() == io::ErrorKind::TimedOut)
+}
+
+#[test]
+fn test_timing_phases() {
+    let ms = Duration::from_millis;
+    let timing = Timing {
+        namelookup: ms(5),
+        connect: ms(15),
+        appconnect: ms(45),
+        pretransfer: ms(46),
+        starttransfer: ms(96),
+        redirect: ms(0),
+        total: ms(100),
+    };
+    assert_eq!(timing.dns(), ms(5));
+    assert_eq!(timing.tcp_connect(), ms(10));
+    assert_eq!(timing.tls_handshake(), ms(30));
+    assert_eq!(timing.ttfb(), ms(50));
+
+    // 明文连接没有 TLS 握手
+    let plain = Timing {
+        appconnect: Duration::ZERO,
+        ..timing
+    };
+    assert_eq!(plain.tls_handshake(), Duration::ZERO);
+
+    let mut data = &b"HTTP/1.1"[..];
+    let mut reader = FirstByte::new(&mut data);
+    assert!(reader.read(&mut []).unwrap() == 0 && reader.at.is_none());
+    assert_eq!(reader.read(&mut [0u8; 4]).unwrap(), 4);
+    assert!(reader.at.is_some());
+}
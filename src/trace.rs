@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// --trace 输出十六进制和 ASCII, --trace-ascii 只输出 ASCII
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    Hex,
+    Ascii,
+}
+
+/// 记录连接上收发的全部数据
+pub struct Trace {
+    out: Box<dyn Write + Send>,
+    format: TraceFormat,
+    time: bool, // --trace-time
+}
+
+impl Trace {
+    pub fn new(out: Box<dyn Write + Send>, format: TraceFormat, time: bool) -> Self {
+        Trace { out, format, time }
+    }
+
+    /// 打开 trace 文件, "-" 表示标准输出
+    pub fn open(path: &str, format: TraceFormat, time: bool) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = if path == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(File::create(path)?)
+        };
+        Ok(Trace::new(out, format, time))
+    }
+
+    pub fn info(&mut self, msg: &str) {
+        let line = format!("{}== Info: {}\n", self.prefix(), msg);
+        let _ = self.out.write_all(line.as_bytes());
+    }
+
+    /// kind 例如 "=> Send header", "<= Recv data"
+    pub fn data(&mut self, kind: &str, data: &[u8]) {
+        if data.is_empty() {
+            return;
+        }
+        let mut text = format!(
+            "{}{}, {} bytes (0x{:x})\n",
+            self.prefix(),
+            kind,
+            data.len(),
+            data.len()
+        );
+        text.push_str(&dump(self.format, data));
+        let _ = self.out.write_all(text.as_bytes());
+        let _ = self.out.flush();
+    }
+
+    fn prefix(&self) -> String {
+        if self.time {
+            timestamp() + " "
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// 与 curl 相同的格式: 偏移, 十六进制, ASCII.
+/// ASCII 格式每行最多 64 字节, 遇到 CRLF 换行
+fn dump(format: TraceFormat, data: &[u8]) -> String {
+    let width = match format {
+        TraceFormat::Hex => 16,
+        TraceFormat::Ascii => 64,
+    };
+    let printable = |b: u8| {
+        if (0x20..0x7f).contains(&b) {
+            b as char
+        } else {
+            '.'
+        }
+    };
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < data.len() {
+        let mut line = &data[i..data.len().min(i + width)];
+        let mut next = i + line.len();
+        if format == TraceFormat::Ascii {
+            if let Some(pos) = line.windows(2).position(|w| w == b"\r\n") {
+                line = &line[..pos];
+                next = i + pos + 2;
+            }
+        }
+        out.push_str(&format!("{:04x}: ", i));
+        if format == TraceFormat::Hex {
+            for b in line {
+                out.push_str(&format!("{:02x} ", b));
+            }
+            out.push_str(&"   ".repeat(width - line.len()));
+        }
+        out.extend(line.iter().map(|&b| printable(b)));
+        out.push('\n');
+        i = next;
+    }
+    out
+}
+
+/// 当前时间 HH:MM:SS.micros (UTC)
+pub fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs() % 86400;
+    format!(
+        "{:02}:{:02}:{:02}.{:06}",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60,
+        now.subsec_micros()
+    )
+}
+
+/// 区分报文的头部和数据: 第一个空行之前为头部
+#[derive(Default)]
+struct HeadSplitter {
+    done: bool,
+    tail: Vec<u8>, // 上一块末尾, 用于查找跨块的 "\r\n\r\n"
+}
+
+impl HeadSplitter {
+    /// 返回 data 中属于头部的长度
+    fn head_len(&mut self, data: &[u8]) -> usize {
+        if self.done {
+            return 0;
+        }
+        let mut joined = std::mem::take(&mut self.tail);
+        let skip = joined.len();
+        joined.extend_from_slice(data);
+        match joined.windows(4).position(|w| w == b"\r\n\r\n") {
+            Some(pos) => {
+                self.done = true;
+                pos + 4 - skip
+            }
+            None => {
+                self.tail = joined[joined.len().saturating_sub(3)..].to_vec();
+                data.len()
+            }
+        }
+    }
+}
+
+/// 包装明文或 TLS 连接, 把经过的数据写入 trace
+pub struct TraceStream<'a, S: Read + Write> {
+    inner: &'a mut S,
+    trace: Arc<Mutex<Trace>>,
+    send: HeadSplitter,
+    recv: HeadSplitter,
+}
+
+impl<'a, S: Read + Write> TraceStream<'a, S> {
+    pub fn new(inner: &'a mut S, trace: Arc<Mutex<Trace>>) -> Self {
+        TraceStream {
+            inner,
+            trace,
+            send: HeadSplitter::default(),
+            recv: HeadSplitter::default(),
+        }
+    }
+
+    fn log(&self, send: bool, head_len: usize, data: &[u8]) {
+        let Ok(mut trace) = self.trace.lock() else {
+            return;
+        };
+        let (header, body) = if send {
+            ("=> Send header", "=> Send data")
+        } else {
+            ("<= Recv header", "<= Recv data")
+        };
+        trace.data(header, &data[..head_len]);
+        trace.data(body, &data[head_len..]);
+    }
+}
+
+impl<S: Read + Write> Read for TraceStream<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let head_len = self.recv.head_len(&buf[..n]);
+        self.log(false, head_len, &buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<S: Read + Write> Write for TraceStream<'_, S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        let head_len = self.send.head_len(&buf[..n]);
+        self.log(true, head_len, &buf[..n]);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[test]
+fn test_trace_dump() {
+    let data = b"GET / HTTP/1.1\r\nHost: a\r\n\r\n";
+    assert_eq!(
+        dump(TraceFormat::Hex, &data[..18]),
+        "0000: 47 45 54 20 2f 20 48 54 54 50 2f 31 2e 31 0d 0a GET / HTTP/1.1..\n\
+         0010: 48 6f                                           Ho\n"
+    );
+    assert_eq!(
+        dump(TraceFormat::Ascii, data),
+        "0000: GET / HTTP/1.1\n0010: Host: a\n0019: \n"
+    );
+
+    // "\r\n\r\n" 跨越两次读取
+    let mut splitter = HeadSplitter::default();
+    assert_eq!(splitter.head_len(b"HTTP/1.1 200 OK\r\n\r"), 18);
+    assert_eq!(splitter.head_len(b"\nbody"), 1);
+    assert_eq!(splitter.head_len(b"more"), 0);
+}
@@ -0,0 +1,216 @@
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use crate::timing::Timing;
+
+/// 一次传输 (包括重定向) 的统计信息, 供 -w/--write-out 输出
+#[derive(Clone, Debug)]
+pub struct TransferInfo {
+    start: Instant,
+    pub http_code: u16,
+    pub content_type: Option<String>,
+    pub url_effective: String,
+    pub remote_addr: Option<SocketAddr>,
+    pub num_redirects: usize,
+    pub num_retries: u32,
+    pub size_download: u64, // 响应体字节数, 不含响应头
+    pub size_upload: u64,   // 请求体字节数
+    pub size_header: u64,   // 响应头字节数
+    pub size_request: u64,  // 请求头和请求体字节数
+    pub timing: Timing,
+}
+
+impl TransferInfo {
+    pub fn new() -> Self {
+        TransferInfo {
+            start: Instant::now(),
+            http_code: 0,
+            content_type: None,
+            url_effective: String::new(),
+            remote_addr: None,
+            num_redirects: 0,
+            num_retries: 0,
+            size_download: 0,
+            size_upload: 0,
+            size_header: 0,
+            size_request: 0,
+            timing: Timing::default(),
+        }
+    }
+
+    /// 距传输开始经过的时间
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// 从传输开始到 instant 的时间
+    pub fn since_start(&self, instant: Instant) -> Duration {
+        instant.saturating_duration_since(self.start)
+    }
+
+    fn variables(&self) -> Vec<(&'static str, Value)> {
+        let speed = |size: u64| {
+            let secs = self.timing.total.as_secs_f64();
+            Value::Int(if secs > 0.0 {
+                (size as f64 / secs) as u64
+            } else {
+                0
+            })
+        };
+        vec![
+            ("content_type", Value::Str(self.content_type.clone())),
+            ("http_code", Value::Code(self.http_code)),
+            ("num_redirects", Value::Int(self.num_redirects as u64)),
+            ("num_retries", Value::Int(self.num_retries as u64)),
+            (
+                "remote_ip",
+                Value::Str(self.remote_addr.map(|a| a.ip().to_string())),
+            ),
+            (
+                "remote_port",
+                Value::Int(self.remote_addr.map(|a| a.port()).unwrap_or(0) as u64),
+            ),
+            ("size_download", Value::Int(self.size_download)),
+            ("size_header", Value::Int(self.size_header)),
+            ("size_request", Value::Int(self.size_request)),
+            ("size_upload", Value::Int(self.size_upload)),
+            ("speed_download", speed(self.size_download)),
+            ("speed_upload", speed(self.size_upload)),
+            ("time_appconnect", Value::Time(self.timing.appconnect)),
+            ("time_connect", Value::Time(self.timing.connect)),
+            ("time_namelookup", Value::Time(self.timing.namelookup)),
+            ("time_pretransfer", Value::Time(self.timing.pretransfer)),
+            ("time_redirect", Value::Time(self.timing.redirect)),
+            ("time_starttransfer", Value::Time(self.timing.starttransfer)),
+            ("time_total", Value::Time(self.timing.total)),
+            (
+                "url_effective",
+                Value::Str(Some(self.url_effective.clone())),
+            ),
+        ]
+    }
+
+    /// %{json}: 所有变量组成的 JSON 对象
+    fn to_json(&self) -> String {
+        let fields: Vec<String> = self
+            .variables()
+            .into_iter()
+            .map(|(name, value)| format!("\"{}\":{}", name, value.json()))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+enum Value {
+    Str(Option<String>),
+    Int(u64),
+    Code(u16), // 状态码固定三位, 没有响应时为 000
+    Time(Duration),
+}
+
+impl Value {
+    fn text(&self) -> String {
+        match self {
+            Value::Str(s) => s.clone().unwrap_or_default(),
+            Value::Int(n) => n.to_string(),
+            Value::Code(code) => format!("{:03}", code),
+            Value::Time(t) => format!("{:.6}", t.as_secs_f64()),
+        }
+    }
+
+    fn json(&self) -> String {
+        match self {
+            Value::Str(Some(s)) => json_string(s),
+            Value::Str(None) => "null".to_string(),
+            Value::Int(n) => n.to_string(),
+            Value::Code(code) => code.to_string(),
+            Value::Time(t) => format!("{:.6}", t.as_secs_f64()),
+        }
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// 展开 -w 模板中的 %{变量}, %% 以及 \n \r \t 转义
+pub fn render(template: &str, info: &TransferInfo) -> String {
+    let variables = info.variables();
+    let mut out = String::new();
+    let mut rest = template;
+    while let Some(c) = rest.chars().next() {
+        if let Some(after) = rest.strip_prefix("%{") {
+            if let Some(end) = after.find('}') {
+                let name = &after[..end];
+                if name == "json" {
+                    out.push_str(&info.to_json());
+                } else if let Some((_, value)) = variables.iter().find(|(n, _)| *n == name) {
+                    out.push_str(&value.text());
+                } else {
+                    eprintln!("Warning: unknown --write-out variable: '{}'", name);
+                }
+                rest = &after[end + 1..];
+                continue;
+            }
+        }
+        let (text, len) = match rest.get(..2) {
+            Some("%%") => ("%", 2),
+            Some("\\n") => ("\n", 2),
+            Some("\\r") => ("\r", 2),
+            Some("\\t") => ("\t", 2),
+            _ => ("", 0),
+        };
+        if len > 0 {
+            out.push_str(text);
+            rest = &rest[len..];
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+#[test]
+fn test_write_out() {
+    let mut info = TransferInfo::new();
+    info.http_code = 200;
+    info.url_effective = "http://example.com/".to_string();
+    info.remote_addr = Some("127.0.0.1:8080".parse().unwrap());
+    info.size_download = 1000;
+    info.timing.total = Duration::from_millis(500);
+
+    assert_eq!(
+        render(
+            "%{http_code} %{time_total} %{size_download} %{speed_download}\\n",
+            &info
+        ),
+        "200 0.500000 1000 2000\n"
+    );
+    assert_eq!(
+        render("%{remote_ip}:%{remote_port} 100%% %{nope}%{", &info),
+        "127.0.0.1:8080 100% %{"
+    );
+    assert_eq!(render("%{http_code}", &TransferInfo::new()), "000");
+
+    info.content_type = Some("text/plain; charset=\"utf-8\"".to_string());
+    let json = render("%{json}", &info);
+    assert!(json.starts_with("{\"content_type\":\"text/plain; charset=\\\"utf-8\\\"\","));
+    assert!(json.contains("\"http_code\":200,"));
+    assert!(json.contains("\"remote_ip\":\"127.0.0.1\""));
+    assert!(json.ends_with("\"url_effective\":\"http://example.com/\"}"));
+    assert!(render("%{json}", &TransferInfo::new()).contains("\"content_type\":null"));
+}